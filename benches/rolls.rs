@@ -0,0 +1,80 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use diceroll::calculation::limits::EvalLimits;
+use diceroll::calculation::{FunctionRegistry, RollRequest};
+use diceroll::controller::batch::roll_batch;
+use diceroll::controller::{evaluate_with_functions, CompiledRoll};
+use diceroll::parsing::math_ops::expression_parse;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Expressions that are typical of what gets rolled, from a single die to a
+/// pool large enough to be counted per face
+const EXPRESSIONS: &[&str] = &[
+    "1d20 + 5",
+    "4d6 k3",
+    "8d10 >= 8",
+    "10d6 e6 k5",
+    "2d20 l1 + 3 >= 15",
+    "max(3d6, 2d8 + 2)",
+    "1000d6 r100 k500",
+    "1000000d6 + 0",
+];
+
+fn expressions(c: &mut Criterion) {
+    let functions = FunctionRegistry::new();
+    let mut group = c.benchmark_group("expressions");
+    for text in EXPRESSIONS {
+        let (_, expression) = expression_parse(text).expect("Benchmark expressions are valid");
+        let mut rng = StdRng::seed_from_u64(0);
+        group.bench_function(*text, |b| {
+            b.iter(|| evaluate_with_functions(black_box(&expression), &mut rng, &functions))
+        });
+    }
+    group.finish();
+}
+
+fn compiled(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compiled");
+    for text in EXPRESSIONS {
+        let roll: CompiledRoll = text.parse().expect("Benchmark expressions are valid");
+        let mut rng = StdRng::seed_from_u64(0);
+        group.bench_function(*text, |b| b.iter(|| black_box(&roll).roll(&mut rng)));
+    }
+    group.finish();
+}
+
+fn roll_request(c: &mut Criterion) {
+    let mut group = c.benchmark_group("roll_request");
+    group.bench_function("reused 100d6 k50 r10", |b| {
+        let mut request = RollRequest::new(StdRng::seed_from_u64(0));
+        b.iter(|| {
+            request.reset().roll_dice(100, 6).keep(50).remove(10);
+            black_box(request.dice_count())
+        })
+    });
+    group.bench_function("new 100d6 k50 r10", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        b.iter(|| {
+            let mut request = RollRequest::new(&mut rng);
+            request.roll_dice(100, 6).keep(50).remove(10);
+            black_box(request.as_vec())
+        })
+    });
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    let functions = FunctionRegistry::new();
+    let limits = EvalLimits::default();
+    let (_, expression) =
+        expression_parse("1d20 + 5 >= 15").expect("Benchmark expressions are valid");
+    let mut group = c.benchmark_group("batch");
+    group.sample_size(10);
+    group.bench_function("100000 rolls of 1d20 + 5 >= 15", |b| {
+        b.iter(|| roll_batch(&expression, 100_000, 0, 0, &functions, &limits))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, expressions, compiled, roll_request, batch);
+criterion_main!(benches);
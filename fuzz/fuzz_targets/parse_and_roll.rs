@@ -0,0 +1,29 @@
+#![no_main]
+
+use diceroll::calculation::limits::EvalLimits;
+use diceroll::calculation::FunctionRegistry;
+use diceroll::controller::{parse_and_roll_symbols, parse_and_roll_with};
+use diceroll::parsing::math_ops::expression_parse;
+use libfuzzer_sys::fuzz_target;
+
+// Any text can be rolled without panicking, and any expression that parses is
+// written out in a form that parses back to the same expression
+fuzz_target!(|data: &[u8]| {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let limits = EvalLimits {
+        max_total_dice: 10_000,
+        max_pooled_dice: 1_000_000,
+        max_total_pooled_dice: 1_000_000,
+        ..EvalLimits::default()
+    };
+    let _ = parse_and_roll_with(text, &FunctionRegistry::new(), &limits);
+    let _ = parse_and_roll_symbols(text);
+
+    if let Ok(("", expression)) = expression_parse(text) {
+        let formatted = expression.to_string();
+        assert_eq!(expression_parse(&formatted), Ok(("", expression)));
+    }
+});
@@ -0,0 +1,413 @@
+use super::die_source::DieSource;
+use super::RollRequest;
+use std::fmt;
+
+/// The result of a statistical test: the statistic, and how likely a statistic
+/// at least that extreme is from a fair die
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+impl TestResult {
+    /// Whether the test found no evidence against the die at significance `alpha`
+    pub fn passed(&self, alpha: f64) -> bool {
+        self.p_value >= alpha
+    }
+}
+
+/// How a die held up to tests for fairness
+#[derive(PartialEq, Debug, Clone)]
+pub struct AuditReport {
+    pub dice_range: u64,
+    pub rolls: u64,
+    /// How many times each face came up, from 1 up to `dice_range`
+    pub counts: Vec<u64>,
+    /// Pearson's chi-square test that every face is equally likely
+    pub uniformity: TestResult,
+    /// Whether each roll is correlated with the roll before it. The statistic is
+    /// the correlation coefficient.
+    pub serial_correlation: TestResult,
+    /// Wald-Wolfowitz runs test of rolls above and below the middle face. The
+    /// statistic is the number of runs.
+    pub runs: TestResult,
+}
+
+impl AuditReport {
+    /// Whether every test passed at significance `alpha`
+    pub fn passed(&self, alpha: f64) -> bool {
+        [self.uniformity, self.serial_correlation, self.runs]
+            .iter()
+            .all(|test| test.passed(alpha))
+    }
+}
+
+/// Roll a `dice_range` sided die `rolls` times through `RollRequest` and test
+/// the rolls for uniformity, serial correlation and runs. Any `Rng` or other
+/// `DieSource` can be audited, so custom random number generators can be
+/// checked too.
+///
+/// ```
+/// use diceroll::calculation::audit::audit;
+///
+/// let report = audit(rand::thread_rng(), 6, 60_000).unwrap();
+/// assert_eq!(report.counts.iter().sum::<u64>(), 60_000);
+/// assert!(report.uniformity.p_value > 0.0);
+/// ```
+///
+/// A die with only one face can't be audited, and every face needs to come up
+/// about 5 times for the tests to mean anything.
+///
+/// ```
+/// use diceroll::calculation::audit::audit;
+///
+/// assert!(audit(rand::thread_rng(), 1, 1_000).is_err());
+/// assert!(audit(rand::thread_rng(), 100, 400).is_err());
+/// ```
+pub fn audit<R: DieSource>(
+    rng: R,
+    dice_range: u64,
+    rolls: u64,
+) -> Result<AuditReport, &'static str> {
+    if dice_range < 2 {
+        return Err("A die needs at least 2 faces to be audited");
+    }
+    if rolls / dice_range < 5 {
+        return Err("Not enough rolls to audit the die");
+    }
+
+    // Rolls are sorted, so the dice are rolled one at a time to keep their order
+    let mut request = RollRequest::new(rng);
+    if let Some(exceeded) = request.roll_dice(0, dice_range).exceeded() {
+        return Err(exceeded.message());
+    }
+    let mut tally = Tally::new(dice_range);
+    for _ in 0..rolls {
+        tally.add(request.roll_die(dice_range));
+    }
+    if let Some(message) = request.failure() {
+        return Err(message);
+    }
+
+    Ok(AuditReport {
+        dice_range,
+        rolls,
+        uniformity: tally.uniformity(),
+        serial_correlation: tally.serial_correlation(),
+        runs: tally.runs(),
+        counts: tally.counts,
+    })
+}
+
+/// Running sums of the rolls, so the rolls themselves don't need to be kept
+struct Tally {
+    counts: Vec<u64>,
+    rolls: u64,
+    sum: u128,
+    sum_of_squares: u128,
+    /// The sum of every roll multiplied by the roll after it
+    sum_of_products: u128,
+    first: u64,
+    last: Option<u64>,
+    /// Twice the middle face, so it stays a whole number
+    middle: u64,
+    above: u64,
+    below: u64,
+    runs: u64,
+    last_side: Option<bool>,
+}
+
+impl Tally {
+    fn new(dice_range: u64) -> Tally {
+        Tally {
+            counts: vec![0; dice_range as usize],
+            rolls: 0,
+            sum: 0,
+            sum_of_squares: 0,
+            sum_of_products: 0,
+            first: 0,
+            last: None,
+            middle: dice_range + 1,
+            above: 0,
+            below: 0,
+            runs: 0,
+            last_side: None,
+        }
+    }
+
+    fn add(&mut self, die: i64) {
+        let die = die as u64;
+        self.counts[(die - 1) as usize] += 1;
+        self.rolls += 1;
+        self.sum += die as u128;
+        self.sum_of_squares += (die as u128) * (die as u128);
+        match self.last {
+            Some(last) => self.sum_of_products += (last as u128) * (die as u128),
+            None => self.first = die,
+        }
+        self.last = Some(die);
+
+        // Rolls on the middle face of an odd die are left out of the runs
+        if die * 2 != self.middle {
+            let side = die * 2 > self.middle;
+            if side {
+                self.above += 1;
+            } else {
+                self.below += 1;
+            }
+            if self.last_side != Some(side) {
+                self.runs += 1;
+            }
+            self.last_side = Some(side);
+        }
+    }
+
+    fn uniformity(&self) -> TestResult {
+        let expected = self.rolls as f64 / self.counts.len() as f64;
+        let statistic: f64 = self
+            .counts
+            .iter()
+            .map(|count| (*count as f64 - expected).powi(2) / expected)
+            .sum();
+        let freedom = (self.counts.len() - 1) as f64;
+        TestResult {
+            statistic,
+            p_value: upper_gamma(freedom / 2.0, statistic / 2.0),
+        }
+    }
+
+    fn serial_correlation(&self) -> TestResult {
+        let n = self.rolls as f64;
+        let mean = self.sum as f64 / n;
+        let last = self.last.unwrap_or(0) as f64;
+        // Sums over the rolls that have a roll after them, and a roll before them
+        let leading = self.sum as f64 - last;
+        let trailing = self.sum as f64 - self.first as f64;
+        let covariance =
+            self.sum_of_products as f64 - mean * (leading + trailing) + (n - 1.0) * mean * mean;
+        let variance = self.sum_of_squares as f64 - n * mean * mean;
+        let statistic = if variance > 0.0 {
+            covariance / variance
+        } else {
+            1.0
+        };
+        // The coefficient of independent rolls is close to normal, with a mean
+        // of -1/n and a variance of 1/n
+        TestResult {
+            statistic,
+            p_value: normal_two_sided((statistic + 1.0 / n) * n.sqrt()),
+        }
+    }
+
+    fn runs(&self) -> TestResult {
+        let (above, below) = (self.above as f64, self.below as f64);
+        let n = above + below;
+        let expected = 2.0 * above * below / n + 1.0;
+        let variance = 2.0 * above * below * (2.0 * above * below - n) / (n * n * (n - 1.0));
+        let statistic = self.runs as f64;
+        let p_value = if variance > 0.0 {
+            normal_two_sided((statistic - expected) / variance.sqrt())
+        } else {
+            0.0
+        };
+        TestResult { statistic, p_value }
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Audit of {} rolls of a d{}", self.rolls, self.dice_range)?;
+        if self.counts.len() <= 20 {
+            for (face, count) in self.counts.iter().enumerate() {
+                writeln!(f, "  {:>2}: {}", face + 1, count)?;
+            }
+        }
+        writeln!(
+            f,
+            "Chi-square uniformity: chi-square = {:.3} with {} degrees of freedom, p = {:.4}",
+            self.uniformity.statistic,
+            self.counts.len() - 1,
+            self.uniformity.p_value
+        )?;
+        writeln!(
+            f,
+            "Serial correlation: r = {:.5}, p = {:.4}",
+            self.serial_correlation.statistic, self.serial_correlation.p_value
+        )?;
+        writeln!(
+            f,
+            "Runs above and below the middle: {} runs, p = {:.4}",
+            self.runs.statistic, self.runs.p_value
+        )?;
+        if self.passed(0.01) {
+            write!(f, "No evidence of bias at the 1% level")
+        } else {
+            write!(f, "Evidence of bias at the 1% level")
+        }
+    }
+}
+
+/// The chance that a standard normal variable is at least `z` away from 0
+fn normal_two_sided(z: f64) -> f64 {
+    upper_gamma(0.5, z * z / 2.0)
+}
+
+/// The regularized upper incomplete gamma function Q(a, x), which is the chance
+/// that a chi-square variable with 2a degrees of freedom is more than 2x
+fn upper_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    if x < a + 1.0 {
+        1.0 - lower_gamma_series(a, x)
+    } else {
+        upper_gamma_fraction(a, x)
+    }
+}
+
+const EPSILON: f64 = 1e-15;
+const MAX_ITERATIONS: usize = 10_000;
+
+/// The regularized lower incomplete gamma function by its series, which
+/// converges quickly when x < a + 1
+fn lower_gamma_series(a: f64, x: f64) -> f64 {
+    let mut term = 1.0 / a;
+    let mut sum = term;
+    for n in 1..MAX_ITERATIONS {
+        term *= x / (a + n as f64);
+        sum += term;
+        if term.abs() < sum.abs() * EPSILON {
+            break;
+        }
+    }
+    (sum.ln() - x + a * x.ln() - ln_gamma(a)).exp()
+}
+
+/// The regularized upper incomplete gamma function by Lentz's continued
+/// fraction, which converges quickly when x >= a + 1
+fn upper_gamma_fraction(a: f64, x: f64) -> f64 {
+    let tiny = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / tiny;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < tiny {
+            d = tiny;
+        }
+        c = b + an / c;
+        if c.abs() < tiny {
+            c = tiny;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (a * x.ln() - x - ln_gamma(a)).exp() * h
+}
+
+/// The log of the gamma function, by the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Error, RngCore, SeedableRng};
+
+    /// Gives every number twice in a row, which is uniform but far from random
+    struct Repeating {
+        rng: StdRng,
+        last: Option<u64>,
+    }
+
+    impl RngCore for Repeating {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            match self.last.take() {
+                Some(last) => last,
+                None => {
+                    let next = self.rng.next_u64();
+                    self.last = Some(next);
+                    next
+                }
+            }
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for chunk in dest.chunks_mut(8) {
+                let bytes = self.next_u64().to_le_bytes();
+                chunk.copy_from_slice(&bytes[..chunk.len()]);
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn p_values_match_known_critical_values() {
+        // The 5% critical values of chi-square with 1, 5 and 20 degrees of freedom
+        assert!((upper_gamma(0.5, 3.841 / 2.0) - 0.05).abs() < 1e-3);
+        assert!((upper_gamma(2.5, 11.070 / 2.0) - 0.05).abs() < 1e-3);
+        assert!((upper_gamma(10.0, 31.410 / 2.0) - 0.05).abs() < 1e-3);
+        assert!((upper_gamma(10.0, 10.851 / 2.0) - 0.95).abs() < 1e-3);
+        assert!((normal_two_sided(1.96) - 0.05).abs() < 1e-3);
+        assert!((normal_two_sided(0.0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fair_dice_pass_the_audit() {
+        for dice_range in [2, 6, 7, 20, 100] {
+            let report = audit(StdRng::seed_from_u64(5), dice_range, 200_000).unwrap();
+            assert_eq!(report.counts.len() as u64, dice_range);
+            assert_eq!(report.counts.iter().sum::<u64>(), 200_000);
+            assert!(report.passed(0.001), "{}", report);
+        }
+    }
+
+    #[test]
+    fn biased_dice_fail_the_audit() {
+        // Every roll lands on 1
+        let report = audit(rand::rngs::mock::StepRng::new(0, 1), 6, 60_000).unwrap();
+        assert_eq!(report.counts[0], 60_000);
+        assert!(!report.uniformity.passed(0.001));
+
+        let rng = Repeating {
+            rng: StdRng::seed_from_u64(5),
+            last: None,
+        };
+        let report = audit(rng, 6, 60_000).unwrap();
+        assert!(report.uniformity.passed(0.001), "{}", report);
+        assert!(!report.serial_correlation.passed(0.001));
+        assert!(!report.runs.passed(0.001));
+    }
+}
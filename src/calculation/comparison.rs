@@ -0,0 +1,218 @@
+use super::math_ops::{roll_scalar_to_number, scalar_margin};
+use super::{Rational, RollScalar};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Comparison {
+    Beats,
+    Ties,
+    Loses,
+}
+
+/// One roll compared die-by-die against another, such as an attacker's dice
+/// against a defender's. Each die of `first` is compared with the die in the
+/// same position of `second`, except that a single die in `first` is compared
+/// with every die of `second`. Dice without a partner aren't compared.
+///
+/// ```
+/// use diceroll::calculation::comparison::{Comparison, ComparisonRoll};
+///
+/// // Attacker rolled 6, 3 and defender rolled 4, 3
+/// let contest = ComparisonRoll::new(vec![6, 3], vec![4, 3]);
+/// assert_eq!(contest.comparisons, vec![Comparison::Beats, Comparison::Ties]);
+/// assert_eq!(contest.wins(), 1);
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct ComparisonRoll {
+    pub first: Vec<i64>,
+    pub second: Vec<i64>,
+    pub comparisons: Vec<Comparison>,
+}
+
+impl ComparisonRoll {
+    pub fn new(first: Vec<i64>, second: Vec<i64>) -> ComparisonRoll {
+        let comparisons = if first.len() == 1 {
+            second.iter().map(|y| compare(first[0], *y)).collect()
+        } else {
+            first
+                .iter()
+                .zip(second.iter())
+                .map(|(x, y)| compare(*x, *y))
+                .collect()
+        };
+        ComparisonRoll {
+            first,
+            second,
+            comparisons,
+        }
+    }
+
+    pub fn wins(&self) -> usize {
+        self.count(Comparison::Beats)
+    }
+
+    pub fn ties(&self) -> usize {
+        self.count(Comparison::Ties)
+    }
+
+    pub fn losses(&self) -> usize {
+        self.count(Comparison::Loses)
+    }
+
+    fn count(&self, comparison: Comparison) -> usize {
+        self.comparisons
+            .iter()
+            .filter(|c| **c == comparison)
+            .count()
+    }
+}
+
+/// Who wins a contest when both sides have the same total
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TieBreak {
+    Attacker,
+    Defender,
+    Draw,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ContestWinner {
+    Attacker,
+    Defender,
+    Draw,
+}
+
+/// The totals of both sides of a contest, and how far the attacker is ahead of the defender
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ContestResult {
+    pub attacker: Rational,
+    pub defender: Rational,
+    pub margin: Rational,
+    pub winner: ContestWinner,
+}
+
+/// Resolve an opposed roll between the totals of an attacker and a defender
+///
+/// ```
+/// use diceroll::calculation::comparison::{resolve_contest, ContestWinner, TieBreak};
+/// use diceroll::calculation::{Rational, RollScalar};
+///
+/// let result = resolve_contest(
+///     &RollScalar::Roll(Some(vec![12])),
+///     &RollScalar::Number(Rational::from_integer(12)),
+///     TieBreak::Defender,
+/// )
+/// .unwrap();
+/// assert_eq!(result.margin, Rational::from_integer(0));
+/// assert_eq!(result.winner, ContestWinner::Defender);
+/// ```
+pub fn resolve_contest(
+    attacker: &RollScalar,
+    defender: &RollScalar,
+    tie_break: TieBreak,
+) -> Result<ContestResult, &'static str> {
+    let margin = scalar_margin(attacker, defender)?;
+    let winner = if margin > Rational::from_integer(0) {
+        ContestWinner::Attacker
+    } else if margin < Rational::from_integer(0) {
+        ContestWinner::Defender
+    } else {
+        match tie_break {
+            TieBreak::Attacker => ContestWinner::Attacker,
+            TieBreak::Defender => ContestWinner::Defender,
+            TieBreak::Draw => ContestWinner::Draw,
+        }
+    };
+    Ok(ContestResult {
+        attacker: roll_scalar_to_number(attacker)?,
+        defender: roll_scalar_to_number(defender)?,
+        margin,
+        winner,
+    })
+}
+
+fn compare(first: i64, second: i64) -> Comparison {
+    match first.cmp(&second) {
+        std::cmp::Ordering::Greater => Comparison::Beats,
+        std::cmp::Ordering::Equal => Comparison::Ties,
+        std::cmp::Ordering::Less => Comparison::Loses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dice_are_compared_pairwise() {
+        let contest = ComparisonRoll::new(vec![6, 2, 5], vec![3, 2, 6]);
+
+        assert_eq!(
+            contest.comparisons,
+            vec![Comparison::Beats, Comparison::Ties, Comparison::Loses]
+        );
+        assert_eq!(
+            (contest.wins(), contest.ties(), contest.losses()),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn single_die_is_compared_against_every_die() {
+        let contest = ComparisonRoll::new(vec![5], vec![3, 5, 9]);
+
+        assert_eq!(
+            contest.comparisons,
+            vec![Comparison::Beats, Comparison::Ties, Comparison::Loses]
+        );
+    }
+
+    #[test]
+    fn unpartnered_dice_are_not_compared() {
+        let contest = ComparisonRoll::new(vec![4, 4, 4], vec![1, 6]);
+        assert_eq!(contest.comparisons.len(), 2);
+    }
+
+    #[test]
+    fn contest_winner_follows_the_margin() {
+        let result = resolve_contest(
+            &RollScalar::Roll(Some(vec![15])),
+            &RollScalar::Number(11.into()),
+            TieBreak::Defender,
+        );
+        assert_eq!(
+            result,
+            Ok(ContestResult {
+                attacker: 15.into(),
+                defender: 11.into(),
+                margin: 4.into(),
+                winner: ContestWinner::Attacker,
+            })
+        );
+
+        let result = resolve_contest(
+            &RollScalar::Number(3.into()),
+            &RollScalar::Number(8.into()),
+            TieBreak::Attacker,
+        )
+        .unwrap();
+        assert_eq!(result.margin, (-5).into());
+        assert_eq!(result.winner, ContestWinner::Defender);
+    }
+
+    #[test]
+    fn ties_follow_the_tie_break_policy() {
+        let tie = |tie_break| {
+            resolve_contest(
+                &RollScalar::Number(7.into()),
+                &RollScalar::Number(7.into()),
+                tie_break,
+            )
+            .unwrap()
+            .winner
+        };
+
+        assert_eq!(tie(TieBreak::Attacker), ContestWinner::Attacker);
+        assert_eq!(tie(TieBreak::Defender), ContestWinner::Defender);
+        assert_eq!(tie(TieBreak::Draw), ContestWinner::Draw);
+    }
+}
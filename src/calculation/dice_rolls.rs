@@ -1,42 +1,164 @@
 extern crate rand;
 
-use super::RollRequest;
-use rand::Rng;
+use super::die_source::{highest_face, DieSource};
+use super::face_counts::{FaceCounts, POOL_THRESHOLD};
+use super::limits::{EvalLimits, LimitExceeded};
+use super::{DiceSet, RollRequest, SortOrder, WildDieRoll};
 
-impl<R: Rng> RollRequest<R> {
+impl<R: DieSource> RollRequest<R> {
     /// Generate a new RollRequest, supplying a random number generator
     pub fn new(rng: R) -> RollRequest<R> {
-        RollRequest { rng, result: None }
+        RollRequest {
+            rng,
+            result: None,
+            spare: vec![],
+            pool: None,
+            order: SortOrder::Ascending,
+            limits: EvalLimits::default(),
+            dice_rolled: 0,
+            dice_pooled: 0,
+            exceeded: None,
+            failure: None,
+        }
+    }
+
+    /// Replace the default limits on how many dice can be rolled
+    ///
+    /// ```
+    /// use diceroll::calculation::limits::{EvalLimits, LimitExceeded};
+    /// use diceroll::calculation::RollRequest;
+    /// let mut request = RollRequest::new(rand::thread_rng());
+    /// request.with_limits(EvalLimits {
+    ///     max_dice_per_roll: 10,
+    ///     ..EvalLimits::default()
+    /// });
+    ///
+    /// // Going over a limit leaves no dice and reports the limit
+    /// assert_eq!(request.roll_dice(11, 6).as_slice(), None);
+    /// assert_eq!(
+    ///     request.exceeded(),
+    ///     Some(LimitExceeded::DicePerRoll { limit: 10, requested: 11 })
+    /// );
+    /// ```
+    pub fn with_limits(&mut self, limits: EvalLimits) -> &mut RollRequest<R> {
+        self.limits = limits;
+        self
+    }
+
+    /// Start over for a new roll with the same random number generator and
+    /// limits. The buffer of the old result is kept for the next roll, so
+    /// simulations can roll the same request many times without allocating.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let mut request = RollRequest::new(rand::thread_rng());
+    /// for _ in 0..100_000 {
+    ///     request.reset().roll_dice(4, 6).keep(3);
+    ///     assert_eq!(request.dice_count(), 3);
+    /// }
+    /// ```
+    pub fn reset(&mut self) -> &mut RollRequest<R> {
+        self.drop_result();
+        self.pool = None;
+        self.order = SortOrder::Ascending;
+        self.dice_rolled = 0;
+        self.dice_pooled = 0;
+        self.exceeded = None;
+        self.failure = None;
+        self
+    }
+
+    /// The first limit that the rolls went over, if any
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        self.exceeded
+    }
+
+    /// How many dice have been rolled one at a time so far, counting rerolls and
+    /// explosions. Pooled dice are counted by `dice_pooled` instead.
+    pub fn dice_rolled(&self) -> u64 {
+        self.dice_rolled
+    }
+
+    /// How many dice have been pooled so far, counting explosions
+    pub fn dice_pooled(&self) -> u64 {
+        self.dice_pooled
+    }
+
+    /// Why a die couldn't be rolled, if it couldn't: the die source couldn't give
+    /// one, or the die has no faces. The roll is left without any dice, like a
+    /// roll that goes over a limit.
+    pub fn failure(&self) -> Option<&'static str> {
+        self.failure
+    }
+
+    /// How many dice are in the roll
+    pub fn dice_count(&self) -> u64 {
+        match (&self.pool, &self.result) {
+            (Some(pool), _) => pool.len(),
+            (None, Some(result)) => result.len() as u64,
+            (None, None) => 0,
+        }
     }
 
     /// Generate a vector of `number_of_dice` random integers in `dice_range`
     ///
     /// ```
-    /// use rust_dicegen::calculation::RollRequest;
+    /// use diceroll::calculation::RollRequest;
     /// let rng = rand::thread_rng();
     /// let mut request = RollRequest::new(rng);
     /// // Roll 50d100
-    /// let request = request.roll_dice(50, 100).as_vec_ptr();
-
+    /// let request = request.roll_dice(50, 100).as_slice();
+    ///
     /// // 50 values in the result vector
     /// assert_eq!(request.as_ref().map(|a| a.len()), Some(50));
     /// // All values are between 1 and 100 inclusive
     /// assert_eq!(request
     ///     .map(|x| x.iter().all(|x| *x >= 1 && *x <= 100)), Some(true));
     /// ```
-    pub fn roll_dice<'a>(
-        &'a mut self,
-        number_of_dice: u64,
-        dice_range: u64,
-    ) -> &'a mut RollRequest<R> {
-        let mut roll_result: Vec<u64> = vec![];
+    ///
+    /// Rolls of more than `POOL_THRESHOLD` dice that have fewer faces than there
+    /// are dice are pooled: only the number of dice on each face is kept, and the
+    /// other modifiers work on those counts.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let mut request = RollRequest::new(rand::thread_rng());
+    /// request.roll_dice(1_000_000, 6).keep(3);
+    ///
+    /// assert_eq!(request.dice_count(), 3);
+    /// assert_eq!(request.as_pool().map(|pool| pool.total()), Some(Some(18)));
+    /// ```
+    pub fn roll_dice(&mut self, number_of_dice: u64, dice_range: u64) -> &mut RollRequest<R> {
+        self.pool = None;
+        if !self.check_faces(dice_range) {
+            return self;
+        }
+        if number_of_dice > POOL_THRESHOLD && dice_range < number_of_dice {
+            let checked = self
+                .check_pool(number_of_dice, dice_range)
+                .and_then(|_| self.check_total_pooled(number_of_dice));
+            if let Err(exceeded) = checked {
+                return self.exceed(exceeded);
+            }
+            if let Some(pool) = self.rng.roll_pool(number_of_dice, dice_range) {
+                self.dice_pooled += number_of_dice;
+                self.drop_result();
+                self.pool = Some(pool);
+                return self;
+            }
+        }
+        if let Err(exceeded) = self.check_dice(number_of_dice, dice_range) {
+            return self.exceed(exceeded);
+        }
+        let mut roll_result = self.take_buffer();
+        roll_result.reserve(number_of_dice as usize);
         for _ in 0..number_of_dice {
-            let dice_roll = self.rng.gen_range(1, dice_range + 1);
+            let dice_roll = self.roll_die(dice_range);
             roll_result.push(dice_roll);
         }
-        roll_result.sort();
         self.result = Some(roll_result);
-        self
+        self.sort_result();
+        self.drop_if_failed()
     }
 
     /// Take the results of a roll and "explode" it in the following way:
@@ -45,120 +167,616 @@ impl<R: Rng> RollRequest<R> {
     ///       are counted towards this explosion
     ///
     /// ```
-    /// use rust_dicegen::calculation::RollRequest;
+    /// use diceroll::calculation::RollRequest;
     /// let rng = rand::thread_rng();
     /// let mut request = RollRequest::new(rng);
     ///
     /// // Roll 50d100 and reroll the dice every time it rolls 100
     /// let request = request.roll_dice(50, 100)
     ///                      .explode(100, 100)
-    ///                      .as_vec_ptr();
+    ///                      .as_slice();
     /// ```
-    pub fn explode<'a>(
-        &'a mut self,
-        explode_on_greater: u64,
-        dice_range: u64,
-    ) -> &'a mut RollRequest<R> {
-        if let Some(ref mut unwrapped_result) = self.result {
-            let mut number_of_extra_rolls = unwrapped_result
+    pub fn explode(&mut self, explode_on_greater: i64, dice_range: u64) -> &mut RollRequest<R> {
+        if !self.check_faces(dice_range) {
+            return self;
+        }
+        if let Err(exceeded) = self.check_dice(0, dice_range) {
+            return self.exceed(exceeded);
+        }
+        if let Some(pool) = self.pool.take() {
+            return self.explode_pool(pool, explode_on_greater, dice_range);
+        }
+        if let Some(mut unwrapped_result) = self.result.take() {
+            let number_of_exploding_dice = unwrapped_result
                 .iter()
                 .filter(|x| **x >= explode_on_greater)
                 .count();
-            while number_of_extra_rolls > 0 {
-                let dice_roll = self.rng.gen_range(1, dice_range + 1);
-                if dice_roll < explode_on_greater {
-                    number_of_extra_rolls -= 1;
+            for _ in 0..number_of_exploding_dice {
+                let mut depth = 0;
+                loop {
+                    if let Err(exceeded) = self.check_explosion(depth) {
+                        return self.exceed(exceeded);
+                    }
+                    let dice_roll = self.roll_die(dice_range);
+                    unwrapped_result.push(dice_roll);
+                    depth += 1;
+                    if dice_roll < explode_on_greater {
+                        break;
+                    }
                 }
-                unwrapped_result.push(dice_roll);
             }
-            unwrapped_result.sort();
+            self.result = Some(unwrapped_result);
         }
 
-        self
+        self.sort_result();
+        self.drop_if_failed()
+    }
+
+    /// Take the results of a roll and "compound" it: every die that's greater or
+    /// equal to `explode_on_greater` is rolled again and the new roll is added to
+    /// that die, for as long as the new rolls keep reaching `explode_on_greater`.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    ///
+    /// // Roll 10d6 and add another roll to a die every time it rolls a 6
+    /// let request = request.roll_dice(10, 6)
+    ///                      .compound(6, 6)
+    ///                      .as_slice();
+    ///
+    /// // The number of dice doesn't change
+    /// assert_eq!(request.map(|a| a.len()), Some(10));
+    /// ```
+    pub fn compound(&mut self, explode_on_greater: i64, dice_range: u64) -> &mut RollRequest<R> {
+        // Every roll is at least 1, so a lower threshold would never stop rolling
+        if explode_on_greater <= 1 || !self.check_faces(dice_range) {
+            return self;
+        }
+        if let Err(exceeded) = self.check_dice(0, dice_range) {
+            return self.exceed(exceeded);
+        }
+        self.unpool();
+        if let Some(mut unwrapped_result) = self.result.take() {
+            for dice_roll in unwrapped_result.iter_mut() {
+                let mut last_roll = *dice_roll;
+                let mut depth = 0;
+                while last_roll >= explode_on_greater {
+                    if let Err(exceeded) = self.check_explosion(depth) {
+                        return self.exceed(exceeded);
+                    }
+                    last_roll = self.roll_die(dice_range);
+                    *dice_roll = dice_roll.saturating_add(last_roll);
+                    depth += 1;
+                }
+            }
+            self.result = Some(unwrapped_result);
+        }
+
+        self.sort_result();
+        self.drop_if_failed()
+    }
+
+    /// Make a Savage Worlds trait roll out of the current roll: the trait dice and
+    /// a `wild_range` sided wild die both ace (compound) on their highest face, and
+    /// the best of them is kept as the only value in the roll. It's a critical
+    /// failure when both the best trait die and the wild die come up as a 1.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    ///
+    /// // Roll a d8 trait die with a d6 wild die
+    /// let wild_die_roll = request.roll_dice(1, 8).wild_die(8, 6);
+    ///
+    /// assert_eq!(wild_die_roll.total, wild_die_roll.trait_roll.max(wild_die_roll.wild_roll));
+    /// assert_eq!(request.as_slice(), Some(&[wild_die_roll.total][..]));
+    /// ```
+    pub fn wild_die(&mut self, dice_range: u64, wild_range: u64) -> WildDieRoll {
+        let checked = self
+            .check_dice(0, dice_range)
+            .and_then(|_| self.check_dice(1, wild_range));
+        let refused = match checked {
+            Err(exceeded) => {
+                self.exceed(exceeded);
+                true
+            }
+            Ok(()) => !(self.check_faces(dice_range) && self.check_faces(wild_range)),
+        };
+        if refused {
+            return WildDieRoll {
+                trait_roll: 0,
+                wild_roll: 0,
+                total: 0,
+                used_wild: false,
+                critical_failure: false,
+            };
+        }
+        self.unpool();
+        self.compound(highest_face(dice_range), dice_range);
+        let trait_roll = self
+            .result
+            .as_ref()
+            .and_then(|r| r.iter().max().copied())
+            .unwrap_or(0);
+
+        let wild_roll = self.roll_die(wild_range);
+        self.replace_result(wild_roll);
+        self.compound(highest_face(wild_range), wild_range);
+        let wild_roll = self.result.as_ref().map_or(wild_roll, |r| r[0]);
+
+        let total = trait_roll.max(wild_roll);
+        if self.exceeded.is_some() || self.failure.is_some() {
+            self.drop_result();
+        } else {
+            self.replace_result(total);
+        }
+        WildDieRoll {
+            trait_roll,
+            wild_roll,
+            total,
+            used_wild: wild_roll > trait_roll,
+            critical_failure: trait_roll == 1 && wild_roll == 1,
+        }
     }
 
     /// Remove the lowest `count` values in the roll
     ///
     /// ```
-    /// use rust_dicegen::calculation::RollRequest;
+    /// use diceroll::calculation::RollRequest;
     /// let rng = rand::thread_rng();
     /// let mut request = RollRequest::new(rng);
     /// let request = request.roll_dice(50, 100)
     ///                      .remove(30)
-    ///                      .as_vec_ptr();
+    ///                      .as_slice();
     ///
     /// // Lowest 30 values in the roll are removed
     /// assert_eq!(request.as_ref().map(|a| a.len()), Some(20));
     /// ```
-    pub fn remove<'a>(&'a mut self, count: u64) -> &'a mut RollRequest<R> {
-        if let Some(ref unwrapped_result) = self.result {
-            if unwrapped_result.len() > count as usize {
-                self.result = Some(unwrapped_result[count as usize..].to_vec());
+    pub fn remove(&mut self, count: u64) -> &mut RollRequest<R> {
+        if let Some(ref mut pool) = self.pool {
+            let len = pool.len();
+            if len > count {
+                pool.keep_highest(len - count);
             } else {
-                self.result = None;
+                self.pool = None;
             }
         }
+        let len = self.result.as_ref().map_or(0, |r| r.len());
+        if len > count as usize {
+            self.retain_highest(len - count as usize);
+        } else {
+            self.drop_result();
+        }
         self
     }
 
     /// Keep the highest `count` values in the roll
     ///
     /// ```
-    /// use rust_dicegen::calculation::RollRequest;
+    /// use diceroll::calculation::RollRequest;
     /// let rng = rand::thread_rng();
     /// let mut request = RollRequest::new(rng);
     /// let request = request.roll_dice(50, 100)
     ///                      .keep(20)
-    ///                      .as_vec_ptr();
+    ///                      .as_slice();
     ///
     /// // Highest 20 values in the roll will be kept
     /// assert_eq!(request.as_ref().map(|a| a.len()), Some(20));
     /// ```
-    pub fn keep<'a>(&'a mut self, count: u64) -> &'a mut RollRequest<R> {
-        if let Some(ref unwrapped_result) = self.result {
-            if unwrapped_result.len() > count as usize {
-                let keep_index = unwrapped_result.len() - count as usize;
-                self.result = Some(unwrapped_result[keep_index..].to_vec());
-            }
+    pub fn keep(&mut self, count: u64) -> &mut RollRequest<R> {
+        if let Some(ref mut pool) = self.pool {
+            pool.keep_highest(count);
         }
+        self.retain_highest(count as usize);
         self
     }
 
     /// Keep the lowest `count` values in the roll
     ///
     /// ```
-    /// use rust_dicegen::calculation::RollRequest;
+    /// use diceroll::calculation::RollRequest;
     /// let rng = rand::thread_rng();
     /// let mut request = RollRequest::new(rng);
     /// let request = request.roll_dice(50, 100)
     ///                      .keep_lower(20)
-    ///                      .as_vec_ptr();
+    ///                      .as_slice();
     ///
     /// // Lowest 20 values in the roll will be kept
     /// assert_eq!(request.as_ref().map(|a| a.len()), Some(20));
     /// ```
-    pub fn keep_lower<'a>(&'a mut self, count: u64) -> &'a mut RollRequest<R> {
-        if let Some(ref unwrapped_result) = self.result {
-            if unwrapped_result.len() > count as usize {
-                self.result = Some(unwrapped_result[..count as usize].to_vec());
+    pub fn keep_lower(&mut self, count: u64) -> &mut RollRequest<R> {
+        if let Some(ref mut pool) = self.pool {
+            pool.keep_lowest(count);
+        }
+        self.retain_lowest(count as usize);
+        self
+    }
+
+    /// Raise every value in the roll that's lower than `min` up to `min`
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    /// let request = request.roll_dice(50, 20)
+    ///                      .clamp_min(10)
+    ///                      .as_slice();
+    ///
+    /// // Every value is at least 10
+    /// assert_eq!(request.map(|x| x.iter().all(|x| *x >= 10)), Some(true));
+    /// ```
+    pub fn clamp_min(&mut self, min: i64) -> &mut RollRequest<R> {
+        if let Some(ref mut pool) = self.pool {
+            pool.clamp_min(min);
+        }
+        if let Some(ref mut unwrapped_result) = self.result {
+            for dice_roll in unwrapped_result.iter_mut() {
+                *dice_roll = (*dice_roll).max(min);
+            }
+        }
+        self
+    }
+
+    /// Lower every value in the roll that's higher than `max` down to `max`
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    /// let request = request.roll_dice(50, 20)
+    ///                      .clamp_max(10)
+    ///                      .as_slice();
+    ///
+    /// // Every value is at most 10
+    /// assert_eq!(request.map(|x| x.iter().all(|x| *x <= 10)), Some(true));
+    /// ```
+    pub fn clamp_max(&mut self, max: i64) -> &mut RollRequest<R> {
+        if let Some(ref mut pool) = self.pool {
+            pool.clamp_max(max);
+        }
+        if let Some(ref mut unwrapped_result) = self.result {
+            for dice_roll in unwrapped_result.iter_mut() {
+                *dice_roll = (*dice_roll).min(max);
             }
         }
         self
     }
 
-    pub fn as_vec_ptr<'a>(&'a self) -> Option<&'a Vec<u64>> {
+    /// Sort the roll from lowest to highest. This is the default order of a roll.
+    pub fn sort_ascending(&mut self) -> &mut RollRequest<R> {
+        self.order = SortOrder::Ascending;
+        self.sort_result();
+        self
+    }
+
+    /// Sort the roll from highest to lowest. Later modifiers keep this order.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    /// let request = request.roll_dice(50, 100)
+    ///                      .sort_descending()
+    ///                      .as_slice();
+    ///
+    /// assert_eq!(request.map(|x| x.windows(2).all(|w| w[0] >= w[1])), Some(true));
+    /// ```
+    pub fn sort_descending(&mut self) -> &mut RollRequest<R> {
+        self.order = SortOrder::Descending;
+        self.sort_result();
+        self
+    }
+
+    /// Group the values in the roll by face, largest set first. Sets of the
+    /// same size are ordered from the highest face to the lowest, and
+    /// unmatched dice are reported as sets with a count of 1.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    /// let sets = request.roll_dice(7, 10).sets().unwrap();
+    ///
+    /// // Every die belongs to exactly one set
+    /// assert_eq!(sets.iter().map(|s| s.count).sum::<u64>(), 7);
+    /// // Sets are ordered by size
+    /// assert!(sets.windows(2).all(|w| w[0].count >= w[1].count));
+    /// ```
+    pub fn sets(&self) -> Option<Vec<DiceSet>> {
+        if let Some(ref pool) = self.pool {
+            return Some(pool.sets());
+        }
+        self.result.as_ref().map(|unwrapped_result| {
+            let mut sets: Vec<DiceSet> = vec![];
+            for dice_roll in unwrapped_result {
+                match sets.iter_mut().find(|set| set.face == *dice_roll) {
+                    Some(set) => set.count += 1,
+                    None => sets.push(DiceSet {
+                        face: *dice_roll,
+                        count: 1,
+                    }),
+                }
+            }
+            sets.sort_unstable_by(|a, b| b.count.cmp(&a.count).then(b.face.cmp(&a.face)));
+            sets
+        })
+    }
+
+    /// The dice in the roll. A pooled roll is turned into a list of dice first,
+    /// which goes over the limit when it has more than `max_dice_per_roll` dice.
+    pub fn as_slice(&mut self) -> Option<&[i64]> {
+        self.unpool();
+        self.result.as_deref()
+    }
+
+    /// The dice in the roll, without turning a pooled roll into a list of dice.
+    /// It's `None` both when the roll has no dice, because it went over a limit
+    /// or the die source failed, and when the roll was pooled: `as_pool` tells
+    /// the two apart, and `as_slice` gives the dice of a pooled roll.
+    ///
+    /// ```
+    /// use diceroll::calculation::limits::EvalLimits;
+    /// use diceroll::calculation::RollRequest;
+    /// let mut request = RollRequest::new(rand::thread_rng());
+    /// request.with_limits(EvalLimits {
+    ///     max_dice_per_roll: 20_000,
+    ///     ..EvalLimits::default()
+    /// });
+    ///
+    /// request.roll_dice(15_000, 6);
+    /// assert_eq!(request.as_vec_ptr(), None);
+    /// assert!(request.as_pool().is_some());
+    /// assert_eq!(request.as_slice().map(|dice| dice.len()), Some(15_000));
+    /// ```
+    pub fn as_vec_ptr(&self) -> Option<&Vec<i64>> {
         self.result.as_ref()
     }
 
-    pub fn as_vec<'a>(self) -> Option<Vec<u64>> {
+    pub fn as_vec(mut self) -> Option<Vec<i64>> {
+        self.unpool();
         self.result
     }
+
+    /// The dice counted per face, when the roll was large enough to be pooled
+    pub fn as_pool(&self) -> Option<&FaceCounts> {
+        self.pool.as_ref()
+    }
+
+    /// Roll a single die with faces numbered from 1 to `dice_range`
+    pub(super) fn roll_die(&mut self, dice_range: u64) -> i64 {
+        self.dice_rolled += 1;
+        match self.rng.roll_die(dice_range) {
+            Ok(die) => die,
+            // Every caller checks `failure` before giving back a result, so the
+            // stand-in die is never taken for a real one
+            Err(message) => {
+                self.failure = self.failure.or(Some(message));
+                1
+            }
+        }
+    }
+
+    pub(super) fn check_dice(
+        &self,
+        number_of_dice: u64,
+        dice_range: u64,
+    ) -> Result<(), LimitExceeded> {
+        if number_of_dice > self.limits.max_dice_per_roll {
+            Err(LimitExceeded::DicePerRoll {
+                limit: self.limits.max_dice_per_roll,
+                requested: number_of_dice,
+            })
+        } else if dice_range > self.limits.max_faces {
+            Err(LimitExceeded::Faces {
+                limit: self.limits.max_faces,
+                requested: dice_range,
+            })
+        } else {
+            self.check_total(number_of_dice)
+        }
+    }
+
+    /// Check that a die with `dice_range` faces can be rolled at all. A die
+    /// without faces isn't over a limit, it's invalid, so it fails the roll like
+    /// a die the source can't give.
+    fn check_faces(&mut self, dice_range: u64) -> bool {
+        if dice_range == 0 {
+            self.fail("A die needs at least one face");
+        }
+        dice_range > 0
+    }
+
+    /// Check that `number_of_dice` more dice can be rolled without going over
+    /// the total
+    pub(super) fn check_total(&self, number_of_dice: u64) -> Result<(), LimitExceeded> {
+        if self.dice_rolled.saturating_add(number_of_dice) > self.limits.max_total_dice {
+            Err(LimitExceeded::TotalDice {
+                limit: self.limits.max_total_dice,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_pool(&self, number_of_dice: u64, dice_range: u64) -> Result<(), LimitExceeded> {
+        if number_of_dice > self.limits.max_pooled_dice {
+            Err(LimitExceeded::DicePerRoll {
+                limit: self.limits.max_pooled_dice,
+                requested: number_of_dice,
+            })
+        } else {
+            self.check_dice(0, dice_range)
+        }
+    }
+
+    /// Check that `number_of_dice` more dice can be pooled without going over
+    /// the total
+    fn check_total_pooled(&self, number_of_dice: u64) -> Result<(), LimitExceeded> {
+        if self.dice_pooled.saturating_add(number_of_dice) > self.limits.max_total_pooled_dice {
+            Err(LimitExceeded::TotalPooledDice {
+                limit: self.limits.max_total_pooled_dice,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check that one more die can explode after `depth` explosions in a row
+    fn check_explosion(&self, depth: u64) -> Result<(), LimitExceeded> {
+        self.check_explosion_depth(depth)?;
+        self.check_total(1)
+    }
+
+    fn check_explosion_depth(&self, depth: u64) -> Result<(), LimitExceeded> {
+        if depth >= self.limits.max_explosion_depth {
+            Err(LimitExceeded::ExplosionDepth {
+                limit: self.limits.max_explosion_depth,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Explode a pooled roll a round at a time: every die that explodes in a
+    /// round is rolled again together in the next one
+    fn explode_pool(
+        &mut self,
+        mut pool: FaceCounts,
+        explode_on_greater: i64,
+        dice_range: u64,
+    ) -> &mut RollRequest<R> {
+        let mut exploding = pool.count_at_least(explode_on_greater);
+        let mut depth = 0;
+        while exploding > 0 {
+            let checked = self
+                .check_explosion_depth(depth)
+                .and_then(|_| self.check_pool(pool.len().saturating_add(exploding), dice_range))
+                .and_then(|_| self.check_total_pooled(exploding));
+            if let Err(exceeded) = checked {
+                return self.exceed(exceeded);
+            }
+            let rerolls = match self.rng.roll_pool(exploding, dice_range) {
+                Some(rerolls) => rerolls,
+                None => return self.fail("The die source can't roll a pool of dice"),
+            };
+            self.dice_pooled += exploding;
+            exploding = rerolls.count_at_least(explode_on_greater);
+            pool.merge(&rerolls);
+            depth += 1;
+        }
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Turn a pooled roll back into a `Vec` of dice, for the modifiers that need
+    /// to look at each die
+    fn unpool(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let len = pool.len();
+            if len > self.limits.max_dice_per_roll {
+                self.exceed(LimitExceeded::DicePerRoll {
+                    limit: self.limits.max_dice_per_roll,
+                    requested: len,
+                });
+            } else {
+                self.result = Some(pool.to_vec());
+                self.sort_result();
+            }
+        }
+    }
+
+    /// Record the first limit the roll went over and drop the result
+    pub(super) fn exceed(&mut self, exceeded: LimitExceeded) -> &mut RollRequest<R> {
+        self.exceeded = self.exceeded.or(Some(exceeded));
+        self.drop_result();
+        self.pool = None;
+        self
+    }
+
+    /// Record why the die source couldn't give a die and drop the result
+    fn fail(&mut self, message: &'static str) -> &mut RollRequest<R> {
+        self.failure = self.failure.or(Some(message));
+        self.drop_if_failed()
+    }
+
+    /// Drop the result when the die source has failed to give a die
+    fn drop_if_failed(&mut self) -> &mut RollRequest<R> {
+        if self.failure.is_some() {
+            self.drop_result();
+            self.pool = None;
+        }
+        self
+    }
+
+    /// Leave the roll without a result, keeping its buffer for the next roll
+    fn drop_result(&mut self) {
+        if let Some(mut unwrapped_result) = self.result.take() {
+            unwrapped_result.clear();
+            self.spare = unwrapped_result;
+        }
+    }
+
+    /// An empty buffer for a new result, reusing the old result if there is one
+    fn take_buffer(&mut self) -> Vec<i64> {
+        self.drop_result();
+        std::mem::take(&mut self.spare)
+    }
+
+    /// Make `value` the only value in the roll
+    fn replace_result(&mut self, value: i64) {
+        let mut buffer = self.take_buffer();
+        buffer.push(value);
+        self.result = Some(buffer);
+    }
+
+    fn sort_result(&mut self) {
+        if let Some(ref mut unwrapped_result) = self.result {
+            match self.order {
+                SortOrder::Ascending => unwrapped_result.sort_unstable(),
+                SortOrder::Descending => unwrapped_result.sort_unstable_by(|a, b| b.cmp(a)),
+            }
+        }
+    }
+
+    /// Keep the lowest `count` values of the sorted result, in place
+    fn retain_lowest(&mut self, count: usize) {
+        let order = self.order;
+        if let Some(ref mut unwrapped_result) = self.result {
+            if unwrapped_result.len() > count {
+                match order {
+                    SortOrder::Ascending => unwrapped_result.truncate(count),
+                    SortOrder::Descending => {
+                        unwrapped_result.drain(..unwrapped_result.len() - count);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keep the highest `count` values of the sorted result, in place
+    fn retain_highest(&mut self, count: usize) {
+        let order = self.order;
+        if let Some(ref mut unwrapped_result) = self.result {
+            if unwrapped_result.len() > count {
+                match order {
+                    SortOrder::Ascending => {
+                        unwrapped_result.drain(..unwrapped_result.len() - count);
+                    }
+                    SortOrder::Descending => unwrapped_result.truncate(count),
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+    use rand::SeedableRng;
 
     #[test]
     fn roll_request_returns_roll_result() {
@@ -174,7 +792,7 @@ mod tests {
 
     #[test]
     fn proper_number_of_elements_in_exploded_dice_roll() {
-        let rng = rand::thread_rng();
+        let rng = rand::rngs::StdRng::seed_from_u64(1);
         let mut request = RollRequest::new(rng);
 
         let number_of_rolls = 10000;
@@ -194,7 +812,7 @@ mod tests {
             };
 
         assert_eq!(
-            request.result.unwrap().iter().count() as u64,
+            request.result.unwrap().len() as u64,
             expected_number_of_rolls
         );
     }
@@ -288,4 +906,338 @@ mod tests {
         let removed_result = request.result.clone().unwrap();
         assert_eq!(*roll_result.iter().min().unwrap(), removed_result[0]);
     }
+
+    #[test]
+    fn clamp_min_raises_low_values() {
+        let rng = rand::rngs::mock::StepRng::new(2, 1);
+        let mut request = RollRequest::new(rng);
+
+        request.roll_dice(5, 10).clamp_min(3);
+        assert_eq!(request.result, Some(vec![3, 3, 3, 3, 3]));
+    }
+
+    #[test]
+    fn clamp_max_lowers_high_values() {
+        let rng = rand::thread_rng();
+        let mut request = RollRequest::new(rng);
+
+        request.roll_dice(50, 100).clamp_max(10);
+        let roll_result = request.result.clone().unwrap();
+        assert_eq!(roll_result.len(), 50);
+        assert!(roll_result.iter().all(|x| *x <= 10));
+    }
+
+    #[test]
+    fn keep_and_remove_respect_descending_order() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.result = Some(vec![1, 2, 3, 4, 5]);
+
+        request.sort_descending();
+        assert_eq!(request.result, Some(vec![5, 4, 3, 2, 1]));
+
+        request.keep(4);
+        assert_eq!(request.result, Some(vec![5, 4, 3, 2]));
+
+        request.remove(1);
+        assert_eq!(request.result, Some(vec![5, 4, 3]));
+
+        request.keep_lower(2);
+        assert_eq!(request.result, Some(vec![4, 3]));
+
+        request.sort_ascending();
+        assert_eq!(request.result, Some(vec![3, 4]));
+    }
+
+    #[test]
+    fn sets_group_dice_by_face_in_order_of_size() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.result = Some(vec![1, 4, 4, 7, 9, 9, 9, 10, 10]);
+
+        assert_eq!(
+            request.sets(),
+            Some(vec![
+                DiceSet { face: 9, count: 3 },
+                DiceSet { face: 10, count: 2 },
+                DiceSet { face: 4, count: 2 },
+                DiceSet { face: 7, count: 1 },
+                DiceSet { face: 1, count: 1 },
+            ])
+        );
+
+        request.result = None;
+        assert_eq!(request.sets(), None);
+    }
+
+    #[test]
+    fn compound_adds_rerolls_to_the_same_die() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.result = Some(vec![1, 2, 3]);
+
+        // StepRng always rolls a 1, so each die that reached 2 gets exactly one extra roll
+        request.compound(2, 3);
+        assert_eq!(request.result, Some(vec![1, 3, 4]));
+    }
+
+    #[test]
+    fn compound_ignores_thresholds_every_roll_reaches() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.result = Some(vec![1, 2, 3]);
+
+        request.compound(1, 3);
+        assert_eq!(request.result, Some(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn snake_eyes_on_wild_die_roll_is_a_critical_failure() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        let wild_die_roll = request.roll_dice(1, 8).wild_die(8, 6);
+
+        assert_eq!(
+            wild_die_roll,
+            WildDieRoll {
+                trait_roll: 1,
+                wild_roll: 1,
+                total: 1,
+                used_wild: false,
+                critical_failure: true,
+            }
+        );
+        assert_eq!(request.result, Some(vec![1]));
+    }
+
+    #[test]
+    fn trait_dice_ace_before_being_compared_with_the_wild_die() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.result = Some(vec![8, 8]);
+
+        // Both trait dice ace once on the 8 and then roll a 1
+        let wild_die_roll = request.wild_die(8, 6);
+        assert_eq!(wild_die_roll.trait_roll, 9);
+        assert!(!wild_die_roll.used_wild);
+        assert!(!wild_die_roll.critical_failure);
+    }
+
+    #[test]
+    fn rolls_over_the_limits_leave_no_dice() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.roll_dice(u64::MAX, u64::MAX);
+        assert_eq!(request.result, None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::DicePerRoll {
+                limit: 10_000,
+                requested: u64::MAX
+            })
+        );
+
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.roll_dice(1, u64::MAX);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::Faces {
+                limit: 1_000_000,
+                requested: u64::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn dice_without_faces_leave_no_dice() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(1_000_000, 0);
+        assert_eq!(request.result, None);
+        assert_eq!(request.pool, None);
+        assert_eq!(request.exceeded(), None);
+        assert_eq!(request.failure(), Some("A die needs at least one face"));
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(1, 6).wild_die(6, 0);
+        assert_eq!(request.result, None);
+        assert_eq!(request.exceeded(), None);
+        assert_eq!(request.failure(), Some("A die needs at least one face"));
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(2, 6).explode(6, 0);
+        assert_eq!(request.result, None);
+        assert_eq!(request.failure(), Some("A die needs at least one face"));
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_die(0);
+        assert_eq!(request.failure(), Some("A die needs at least one face"));
+    }
+
+    #[test]
+    fn total_dice_are_counted_across_rolls() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.with_limits(EvalLimits {
+            max_total_dice: 5,
+            ..EvalLimits::default()
+        });
+
+        request.roll_dice(3, 6).roll_dice(2, 6);
+        assert_eq!(request.dice_rolled(), 5);
+        assert_eq!(request.exceeded(), None);
+
+        request.roll_dice(1, 6);
+        assert_eq!(request.result, None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::TotalDice { limit: 5 })
+        );
+    }
+
+    #[test]
+    fn explosions_stop_at_the_depth_limit() {
+        let limits = EvalLimits {
+            max_explosion_depth: 3,
+            ..EvalLimits::default()
+        };
+
+        // Every roll reaches a threshold of 1, so the dice would explode forever
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.with_limits(limits).roll_dice(2, 6).explode(1, 6);
+        assert_eq!(request.result, None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::ExplosionDepth { limit: 3 })
+        );
+
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+        request.with_limits(limits).roll_dice(2, 6).explode(2, 6);
+        assert_eq!(request.result, Some(vec![1, 1]));
+        assert_eq!(request.exceeded(), None);
+    }
+
+    #[test]
+    fn modifiers_reuse_the_result_buffer() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request
+            .roll_dice(100, 6)
+            .sort_descending()
+            .keep(10)
+            .keep_lower(4);
+        let dice = request.result.clone().unwrap();
+        assert_eq!(dice.len(), 4);
+        assert!(dice.windows(2).all(|w| w[0] >= w[1]));
+        assert!(request.result.as_ref().unwrap().capacity() >= 100);
+
+        // Removing every die keeps the buffer around for the next roll
+        request.remove(4).roll_dice(50, 6);
+        assert_eq!(request.result.as_ref().map(|r| r.len()), Some(50));
+        assert!(request.result.as_ref().unwrap().capacity() >= 100);
+    }
+
+    #[test]
+    fn large_rolls_are_pooled() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(1_000_000, 1).clamp_min(2).remove(999_990);
+        assert_eq!(request.result, None);
+        assert_eq!(request.dice_count(), 10);
+        assert_eq!(request.dice_rolled(), 0);
+        assert_eq!(request.as_vec_ptr(), None);
+        assert_eq!(request.sets(), Some(vec![DiceSet { face: 2, count: 10 }]));
+        assert_eq!(request.as_vec(), Some(vec![2; 10]));
+
+        // Pooled dice count towards their own total
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.with_limits(EvalLimits {
+            max_total_pooled_dice: 1_500_000,
+            ..EvalLimits::default()
+        });
+        request.roll_dice(1_000_000, 6);
+        assert_eq!(request.dice_pooled(), 1_000_000);
+        request.roll_dice(1_000_000, 6);
+        assert_eq!(request.as_pool(), None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::TotalPooledDice { limit: 1_500_000 })
+        );
+
+        // A roll at the threshold still keeps every die
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(POOL_THRESHOLD, 6);
+        assert_eq!(request.as_pool(), None);
+        assert_eq!(request.dice_count(), POOL_THRESHOLD);
+    }
+
+    #[test]
+    fn pooled_rolls_explode_a_round_at_a_time() {
+        let limits = EvalLimits {
+            max_explosion_depth: 3,
+            ..EvalLimits::default()
+        };
+        let mut request = RollRequest::new(rand::thread_rng());
+        request
+            .with_limits(limits)
+            .roll_dice(100_000, 1)
+            .explode(1, 1);
+        assert_eq!(request.as_pool(), None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::ExplosionDepth { limit: 3 })
+        );
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.roll_dice(100_000, 6).explode(7, 6);
+        assert_eq!(request.dice_count(), 100_000);
+
+        // Turning a pool into a vector of dice is held to the per die limit
+        assert_eq!(request.as_slice(), None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::DicePerRoll {
+                limit: 10_000,
+                requested: 100_000
+            })
+        );
+    }
+
+    /// Small rolls, and rolls just big enough to be pooled
+    fn number_of_dice() -> impl Strategy<Value = u64> {
+        prop_oneof![0u64..200, POOL_THRESHOLD..POOL_THRESHOLD + 100]
+    }
+
+    proptest! {
+        #[test]
+        fn keep_never_grows_the_pool(
+            number_of_dice in number_of_dice(),
+            dice_range in 1u64..50,
+            count in 0u64..300,
+            seed in any::<u64>(),
+        ) {
+            let mut request = RollRequest::new(rand::rngs::StdRng::seed_from_u64(seed));
+            request.roll_dice(number_of_dice, dice_range);
+            let rolled = request.as_pool().map_or_else(
+                || request.result.clone().unwrap_or_default(),
+                FaceCounts::to_vec,
+            );
+            request.keep(count);
+            let kept = request.as_vec().unwrap_or_default();
+
+            prop_assert_eq!(kept.len() as u64, count.min(number_of_dice));
+            // The kept dice are the highest of the roll
+            let mut highest = rolled;
+            highest.sort_unstable_by(|a, b| b.cmp(a));
+            highest.truncate(kept.len());
+            let mut kept = kept;
+            kept.sort_unstable_by(|a, b| b.cmp(a));
+            prop_assert_eq!(kept, highest);
+        }
+
+        #[test]
+        fn keep_lower_and_remove_never_grow_the_pool(
+            number_of_dice in number_of_dice(),
+            dice_range in 1u64..50,
+            count in 0u64..300,
+            removed in 0u64..300,
+            seed in any::<u64>(),
+        ) {
+            let mut request = RollRequest::new(rand::rngs::StdRng::seed_from_u64(seed));
+            request.roll_dice(number_of_dice, dice_range).keep_lower(count);
+            let kept = count.min(number_of_dice);
+            prop_assert_eq!(request.dice_count(), kept);
+            request.remove(removed);
+            prop_assert_eq!(request.dice_count(), kept.saturating_sub(removed));
+        }
+    }
 }
@@ -0,0 +1,202 @@
+use super::face_counts::FaceCounts;
+use rand::Rng;
+use std::io::{BufRead, Write};
+
+/// Where the dice of a `RollRequest` come from. Every random number generator
+/// is a source of fair dice, and `ManualDice` and `PromptedDice` give the dice
+/// that were rolled by hand, so physical dice go through the same keep, drop
+/// and explode modifiers as rolled ones.
+pub trait DieSource {
+    /// A die with faces from 1 to `dice_range`, or why the source has no die
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str>;
+
+    /// `number_of_dice` dice counted per face, for sources that can give a huge
+    /// roll without giving every die. A source that gives a pool once has to
+    /// give a pool every time it's asked.
+    fn roll_pool(&mut self, _number_of_dice: u64, _dice_range: u64) -> Option<FaceCounts> {
+        None
+    }
+}
+
+impl<R: Rng> DieSource for R {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        if dice_range == 0 {
+            return Err("A die needs at least one face");
+        }
+        Ok(self.gen_range(1, highest_face(dice_range) + 1))
+    }
+
+    fn roll_pool(&mut self, number_of_dice: u64, dice_range: u64) -> Option<FaceCounts> {
+        Some(FaceCounts::roll(self, number_of_dice, dice_range))
+    }
+}
+
+/// The highest face of a die that can be rolled as an `i64`
+pub(crate) fn highest_face(dice_range: u64) -> i64 {
+    dice_range.min(i64::MAX as u64 - 1) as i64
+}
+
+/// Dice that were rolled by hand, given in the order they're needed. A value
+/// that isn't on the faces of the die it's given for fails the roll, and so
+/// does running out of values.
+///
+/// ```
+/// use diceroll::calculation::die_source::ManualDice;
+/// use diceroll::calculation::RollRequest;
+///
+/// let mut request = RollRequest::new(ManualDice::new(vec![6, 2, 5, 1, 3]));
+/// // 4d6, exploding the 6 into a 3
+/// request.roll_dice(4, 6).explode(6, 6).keep(3);
+/// assert_eq!(request.as_slice(), Some(&[3, 5, 6][..]));
+///
+/// let mut request = RollRequest::new(ManualDice::new(vec![7]));
+/// assert_eq!(request.roll_dice(1, 6).as_slice(), None);
+/// assert_eq!(request.failure(), Some("A die came up outside its faces"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualDice<I> {
+    values: I,
+}
+
+impl<I: Iterator<Item = i64>> ManualDice<I> {
+    pub fn new<V: IntoIterator<IntoIter = I>>(values: V) -> ManualDice<I> {
+        ManualDice {
+            values: values.into_iter(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = i64>> DieSource for ManualDice<I> {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        match self.values.next() {
+            Some(value) if (1..=highest_face(dice_range)).contains(&value) => Ok(value),
+            Some(_) => Err("A die came up outside its faces"),
+            None => Err("Ran out of dice"),
+        }
+    }
+}
+
+/// Dice that are asked for one at a time, such as from someone at the table
+/// typing in what their physical dice show. Every die is prompted for on
+/// `output`, and asked for again until the answer is on the faces of the die.
+///
+/// ```
+/// use diceroll::calculation::die_source::PromptedDice;
+/// use diceroll::calculation::RollRequest;
+///
+/// let input = "4\n9\n1\n".as_bytes();
+/// let mut prompts = vec![];
+/// let mut request = RollRequest::new(PromptedDice::new(input, &mut prompts));
+/// request.roll_dice(2, 6);
+/// assert_eq!(request.as_vec(), Some(vec![1, 4]));
+/// assert_eq!(
+///     String::from_utf8(prompts).unwrap(),
+///     "d6: d6: Enter a number from 1 to 6\nd6: "
+/// );
+/// ```
+pub struct PromptedDice<B, W> {
+    input: B,
+    output: W,
+    line: String,
+}
+
+impl<B: BufRead, W: Write> PromptedDice<B, W> {
+    pub fn new(input: B, output: W) -> PromptedDice<B, W> {
+        PromptedDice {
+            input,
+            output,
+            line: String::new(),
+        }
+    }
+}
+
+impl<B: BufRead, W: Write> DieSource for PromptedDice<B, W> {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        let highest = highest_face(dice_range);
+        loop {
+            write!(self.output, "d{}: ", dice_range)
+                .and_then(|_| self.output.flush())
+                .map_err(|_| "Couldn't ask for a die")?;
+            self.line.clear();
+            match self.input.read_line(&mut self.line) {
+                Ok(0) | Err(_) => return Err("Ran out of dice"),
+                Ok(_) => (),
+            }
+            match self.line.trim().parse::<i64>() {
+                Ok(value) if (1..=highest).contains(&value) => return Ok(value),
+                _ => writeln!(self.output, "Enter a number from 1 to {}", highest)
+                    .map_err(|_| "Couldn't ask for a die")?,
+            }
+        }
+    }
+}
+
+impl DieSource for Box<dyn DieSource> {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        (**self).roll_die(dice_range)
+    }
+
+    fn roll_pool(&mut self, number_of_dice: u64, dice_range: u64) -> Option<FaceCounts> {
+        (**self).roll_pool(number_of_dice, dice_range)
+    }
+}
+
+/// A source borrowed for a single roll, so one source can be shared by every
+/// roll in an expression
+pub(crate) struct Borrowed<'a, S: ?Sized>(pub(crate) &'a mut S);
+
+impl<S: DieSource + ?Sized> DieSource for Borrowed<'_, S> {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        self.0.roll_die(dice_range)
+    }
+
+    fn roll_pool(&mut self, number_of_dice: u64, dice_range: u64) -> Option<FaceCounts> {
+        self.0.roll_pool(number_of_dice, dice_range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::RollRequest;
+
+    #[test]
+    fn manual_dice_go_through_the_modifiers() {
+        let mut request = RollRequest::new(ManualDice::new(vec![1, 20, 20, 3]));
+        request.roll_dice(2, 20).explode(20, 20).keep_lower(2);
+        assert_eq!(request.as_slice(), Some(&[1, 3][..]));
+        assert_eq!(request.failure(), None);
+
+        // A huge roll can't be pooled, so it's held to the limit on a single roll
+        let mut request = RollRequest::new(ManualDice::new(std::iter::repeat(1)));
+        assert_eq!(request.roll_dice(20_000, 6).as_slice(), None);
+        assert!(request.exceeded().is_some());
+    }
+
+    #[test]
+    fn running_out_of_dice_fails_the_roll() {
+        let mut request = RollRequest::new(ManualDice::new(vec![4, 4]));
+        request.roll_dice(3, 6);
+        assert_eq!(request.as_slice(), None);
+        assert_eq!(request.failure(), Some("Ran out of dice"));
+
+        // The failure lasts until the request is reset
+        request.roll_dice(0, 6);
+        assert_eq!(request.failure(), Some("Ran out of dice"));
+        request.reset();
+        assert_eq!(request.failure(), None);
+    }
+
+    #[test]
+    fn prompts_stop_at_the_end_of_the_input() {
+        let mut prompts = vec![];
+        let mut request = RollRequest::new(PromptedDice::new("x\n3\n".as_bytes(), &mut prompts));
+        request.roll_dice(2, 4);
+        assert_eq!(request.failure(), Some("Ran out of dice"));
+        drop(request);
+        assert_eq!(
+            String::from_utf8(prompts).unwrap(),
+            "d4: Enter a number from 1 to 4\nd4: d4: "
+        );
+    }
+}
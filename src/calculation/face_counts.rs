@@ -0,0 +1,286 @@
+extern crate rand;
+extern crate rand_distr;
+
+use super::DiceSet;
+use rand::Rng;
+use rand_distr::{Binomial, Distribution};
+use std::convert::TryFrom;
+
+/// Rolls of more dice than this are kept as a `FaceCounts` when the dice have
+/// fewer faces than there are dice. Smaller rolls keep every die in a `Vec`.
+pub const POOL_THRESHOLD: u64 = 10_000;
+
+/// A pool of dice kept as how many dice landed on each face, so that rolls like
+/// `1000000d6` take time and memory in proportion to the faces of the dice
+/// rather than the number of dice.
+///
+/// ```
+/// use diceroll::calculation::face_counts::FaceCounts;
+/// let mut pool = FaceCounts::roll(&mut rand::thread_rng(), 1_000_000, 6);
+/// assert_eq!(pool.len(), 1_000_000);
+///
+/// // Keep the highest 10 dice, which are almost certainly all 6s
+/// pool.keep_highest(10);
+/// assert_eq!(pool.len(), 10);
+/// assert!(pool.total().is_some_and(|total| total <= 60));
+/// ```
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FaceCounts {
+    /// Sorted by face from lowest to highest, without any empty faces
+    sets: Vec<DiceSet>,
+}
+
+impl FaceCounts {
+    /// Roll `number_of_dice` dice with faces from 1 to `dice_range`, sampling how
+    /// many dice land on each face instead of rolling the dice one at a time
+    pub fn roll<R: Rng>(rng: &mut R, number_of_dice: u64, dice_range: u64) -> FaceCounts {
+        let mut sets = vec![];
+        let mut remaining = number_of_dice;
+        for face in 1..=dice_range {
+            if remaining == 0 {
+                break;
+            }
+            // Each die that isn't on a lower face is equally likely to be on any
+            // of the faces that are left
+            let count = if face == dice_range {
+                remaining
+            } else {
+                let probability = 1.0 / (dice_range - face + 1) as f64;
+                Binomial::new(remaining, probability).map_or(0, |binomial| binomial.sample(rng))
+            };
+            if count > 0 {
+                sets.push(DiceSet {
+                    face: face as i64,
+                    count,
+                });
+            }
+            remaining -= count;
+        }
+        FaceCounts { sets }
+    }
+
+    /// Count the dice in a list of rolled values
+    pub fn from_dice(dice: &[i64]) -> FaceCounts {
+        FaceCounts::from_sets(
+            dice.iter()
+                .map(|face| DiceSet {
+                    face: *face,
+                    count: 1,
+                })
+                .collect(),
+        )
+    }
+
+    /// How many dice are in the pool
+    pub fn len(&self) -> u64 {
+        self.sets.iter().map(|set| set.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// The sum of every die in the pool, or `None` when it doesn't fit in an `i64`
+    pub fn total(&self) -> Option<i64> {
+        self.sets.iter().try_fold(0i64, |total, set| {
+            i64::try_from(set.count)
+                .ok()
+                .and_then(|count| set.face.checked_mul(count))
+                .and_then(|sum| total.checked_add(sum))
+        })
+    }
+
+    /// How many dice are on `face` or higher
+    pub fn count_at_least(&self, face: i64) -> u64 {
+        self.sets
+            .iter()
+            .filter(|set| set.face >= face)
+            .map(|set| set.count)
+            .sum()
+    }
+
+    /// Keep the highest `count` dice
+    pub fn keep_highest(&mut self, count: u64) {
+        let mut remaining = count;
+        let mut sets: Vec<DiceSet> = self
+            .sets
+            .iter()
+            .rev()
+            .map_while(|set| take(set, &mut remaining))
+            .collect();
+        sets.reverse();
+        self.sets = sets;
+    }
+
+    /// Keep the lowest `count` dice
+    pub fn keep_lowest(&mut self, count: u64) {
+        let mut remaining = count;
+        self.sets = self
+            .sets
+            .iter()
+            .map_while(|set| take(set, &mut remaining))
+            .collect();
+    }
+
+    /// Raise every die that's lower than `min` up to `min`
+    pub fn clamp_min(&mut self, min: i64) {
+        self.map_faces(|face| face.max(min));
+    }
+
+    /// Lower every die that's higher than `max` down to `max`
+    pub fn clamp_max(&mut self, max: i64) {
+        self.map_faces(|face| face.min(max));
+    }
+
+    /// Keep only the dice whose face passes the filter
+    pub fn retain<F: Fn(i64) -> bool>(&mut self, filter: F) {
+        self.sets.retain(|set| filter(set.face));
+    }
+
+    /// Negate every die, or `None` when a die can't be negated
+    pub fn checked_neg(&self) -> Option<FaceCounts> {
+        let mut sets = self
+            .sets
+            .iter()
+            .map(|set| {
+                set.face.checked_neg().map(|face| DiceSet {
+                    face,
+                    count: set.count,
+                })
+            })
+            .collect::<Option<Vec<DiceSet>>>()?;
+        sets.reverse();
+        Some(FaceCounts { sets })
+    }
+
+    /// Add the dice of another pool to this one
+    pub fn merge(&mut self, other: &FaceCounts) {
+        let mut sets = std::mem::take(&mut self.sets);
+        sets.extend_from_slice(&other.sets);
+        *self = FaceCounts::from_sets(sets);
+    }
+
+    /// The dice grouped by face, ordered like `RollRequest::sets`: largest set
+    /// first, then from the highest face to the lowest
+    pub fn sets(&self) -> Vec<DiceSet> {
+        let mut sets = self.sets.clone();
+        sets.sort_unstable_by(|a, b| b.count.cmp(&a.count).then(b.face.cmp(&a.face)));
+        sets
+    }
+
+    /// Every die in the pool, from lowest to highest
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.sets
+            .iter()
+            .flat_map(|set| std::iter::repeat(set.face).take(set.count as usize))
+            .collect()
+    }
+
+    fn map_faces<F: Fn(i64) -> i64>(&mut self, map: F) {
+        let sets = self
+            .sets
+            .iter()
+            .map(|set| DiceSet {
+                face: map(set.face),
+                count: set.count,
+            })
+            .collect();
+        *self = FaceCounts::from_sets(sets);
+    }
+
+    /// Sort the sets by face and combine the sets that share a face
+    fn from_sets(mut sets: Vec<DiceSet>) -> FaceCounts {
+        sets.sort_unstable_by_key(|set| set.face);
+        let mut combined: Vec<DiceSet> = Vec::with_capacity(sets.len());
+        for set in sets.into_iter().filter(|set| set.count > 0) {
+            match combined.last_mut() {
+                Some(last) if last.face == set.face => last.count += set.count,
+                _ => combined.push(set),
+            }
+        }
+        FaceCounts { sets: combined }
+    }
+}
+
+/// Take up to `remaining` dice from the set, stopping once there are none left
+fn take(set: &DiceSet, remaining: &mut u64) -> Option<DiceSet> {
+    if *remaining == 0 {
+        return None;
+    }
+    let count = set.count.min(*remaining);
+    *remaining -= count;
+    Some(DiceSet {
+        face: set.face,
+        count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn seeded() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn rolled_pools_have_every_die() {
+        let pool = FaceCounts::roll(&mut seeded(), 1_000_000, 6);
+        assert_eq!(pool.len(), 1_000_000);
+        assert_eq!(pool.sets.len(), 6);
+        // Every face gets close to a sixth of the dice
+        assert!(pool
+            .sets
+            .iter()
+            .all(|set| set.count > 160_000 && set.count < 173_000));
+
+        let pool = FaceCounts::roll(&mut seeded(), 20_000, 1);
+        assert_eq!(pool.to_vec(), vec![1; 20_000]);
+        assert_eq!(pool.total(), Some(20_000));
+    }
+
+    #[test]
+    fn keep_and_drop_work_on_counts() {
+        let mut pool = FaceCounts::from_dice(&[1, 1, 2, 3, 3, 3, 6]);
+        assert_eq!(pool.count_at_least(3), 4);
+
+        pool.keep_highest(5);
+        assert_eq!(pool.to_vec(), vec![2, 3, 3, 3, 6]);
+        pool.keep_lowest(3);
+        assert_eq!(pool.to_vec(), vec![2, 3, 3]);
+        assert_eq!(pool.total(), Some(8));
+        pool.keep_highest(0);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn changing_faces_merges_counts() {
+        let mut pool = FaceCounts::from_dice(&[1, 2, 4, 5, 6]);
+        pool.clamp_min(3);
+        pool.clamp_max(5);
+        assert_eq!(
+            pool.sets(),
+            vec![
+                DiceSet { face: 5, count: 2 },
+                DiceSet { face: 3, count: 2 },
+                DiceSet { face: 4, count: 1 },
+            ]
+        );
+
+        pool.merge(&FaceCounts::from_dice(&[1, 4]));
+        assert_eq!(pool.to_vec(), vec![1, 3, 3, 4, 4, 5, 5]);
+        assert_eq!(
+            pool.checked_neg().map(|p| p.to_vec()),
+            Some(vec![-5, -5, -4, -4, -3, -3, -1])
+        );
+        pool.retain(|face| face % 2 == 1);
+        assert_eq!(pool.to_vec(), vec![1, 3, 3, 5, 5]);
+    }
+
+    #[test]
+    fn totals_are_checked() {
+        let pool = FaceCounts::from_dice(&[i64::MAX, 1]);
+        assert_eq!(pool.total(), None);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::math_ops::{roll_scalar_to_number, scalar_round};
+use super::{Rational, RollScalar, Rounding};
+
+/// A function that can be called from an expression, given its evaluated arguments
+pub type ScalarFunction =
+    Box<dyn Fn(&[RollScalar]) -> Result<RollScalar, &'static str> + Send + Sync>;
+
+/// The functions that can be called by name in an expression. `new` starts
+/// with the built-in functions, and more can be registered on top of them.
+///
+/// ```
+/// use diceroll::calculation::{FunctionRegistry, Rational, RollScalar};
+/// use diceroll::calculation::math_ops::roll_scalar_to_number;
+///
+/// let mut functions = FunctionRegistry::new();
+/// functions.register("mod", |args| match args {
+///     [score] => roll_scalar_to_number(score)?
+///         .checked_sub(10.into())
+///         .and_then(|difference| difference.checked_floor_div(2.into()))
+///         .map(RollScalar::Number)
+///         .ok_or("Integer overflow"),
+///     _ => Err("Wrong number of arguments"),
+/// });
+///
+/// let score = RollScalar::Roll(Some(vec![6, 5, 4]));
+/// assert_eq!(
+///     functions.call("mod", &[score]),
+///     Ok(RollScalar::Number(Rational::from_integer(2)))
+/// );
+/// ```
+pub struct FunctionRegistry {
+    functions: HashMap<String, ScalarFunction>,
+}
+
+impl FunctionRegistry {
+    /// A registry with the built-in functions:
+    ///
+    /// * `min(...)` and `max(...)`, the smallest or largest total of the arguments
+    /// * `sum(...)`, the total of all the arguments
+    /// * `abs(x)`, the total without its sign
+    /// * `count(...)`, how many dice are in the arguments, so `count(8d10 >= 8)`
+    ///   counts the dice that came up 8 or more. A number counts as one value and
+    ///   a boolean counts when it's true.
+    /// * `avg(...)`, the average of every die and number in the arguments
+    /// * `floor(x)`, `ceil(x)` and `round(x)`, the total rounded to an integer
+    pub fn new() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::empty();
+        registry
+            .register("min", |args| extreme_total(args, std::cmp::min))
+            .register("max", |args| extreme_total(args, std::cmp::max))
+            .register("sum", |args| sum(&values(args)).map(RollScalar::Number))
+            .register("abs", |args| {
+                let number = roll_scalar_to_number(single_argument(args)?)?;
+                number
+                    .checked_abs()
+                    .map(RollScalar::Number)
+                    .ok_or("Integer overflow")
+            })
+            .register("count", |args| count(&values(args)).map(RollScalar::Number))
+            .register("avg", |args| {
+                let values = values(args);
+                let count = count(&values)?;
+                if count.is_zero() {
+                    return Err("Nothing to average");
+                }
+                sum(&values)?
+                    .checked_div(count)
+                    .map(RollScalar::Number)
+                    .ok_or("Integer overflow")
+            })
+            .register("floor", |args| rounded(args, Rounding::Floor))
+            .register("ceil", |args| rounded(args, Rounding::Ceil))
+            .register("round", |args| rounded(args, Rounding::Round));
+        registry
+    }
+
+    /// A registry without any functions
+    pub fn empty() -> FunctionRegistry {
+        FunctionRegistry {
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Add a function, replacing any function with the same name. Names are not
+    /// case sensitive.
+    pub fn register<F>(&mut self, name: &str, function: F) -> &mut Self
+    where
+        F: Fn(&[RollScalar]) -> Result<RollScalar, &'static str> + Send + Sync + 'static,
+    {
+        self.functions
+            .insert(name.to_lowercase(), Box::new(function));
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(&name.to_lowercase())
+    }
+
+    pub fn call(&self, name: &str, args: &[RollScalar]) -> Result<RollScalar, &'static str> {
+        let function = self
+            .functions
+            .get(name)
+            .or_else(|| self.functions.get(&name.to_lowercase()));
+        match function {
+            Some(function) => function(args),
+            None => Err("Unknown function"),
+        }
+    }
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> FunctionRegistry {
+        FunctionRegistry::new()
+    }
+}
+
+fn single_argument(args: &[RollScalar]) -> Result<&RollScalar, &'static str> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err("Wrong number of arguments"),
+    }
+}
+
+fn extreme_total(
+    args: &[RollScalar],
+    pick: fn(Rational, Rational) -> Rational,
+) -> Result<RollScalar, &'static str> {
+    let totals = args
+        .iter()
+        .map(roll_scalar_to_number)
+        .collect::<Result<Vec<Rational>, &'static str>>()?;
+    totals
+        .into_iter()
+        .reduce(pick)
+        .map(RollScalar::Number)
+        .ok_or("Wrong number of arguments")
+}
+
+fn rounded(args: &[RollScalar], rounding: Rounding) -> Result<RollScalar, &'static str> {
+    scalar_round(single_argument(args)?, rounding)
+}
+
+/// Every die of the rolls, every number, and the booleans that are true, along
+/// with how many times each of them appears
+fn values(args: &[RollScalar]) -> Vec<(Rational, u64)> {
+    args.iter()
+        .flat_map(|arg| match arg {
+            RollScalar::Number(number) => vec![(*number, 1)],
+            RollScalar::Roll(rolls) => rolls
+                .iter()
+                .flatten()
+                .map(|x| (Rational::from_integer(*x), 1))
+                .collect(),
+            RollScalar::Pool(pool) => pool
+                .sets()
+                .iter()
+                .map(|set| (Rational::from_integer(set.face), set.count))
+                .collect(),
+            RollScalar::Bool(true) => vec![(Rational::from_integer(1), 1)],
+            RollScalar::Bool(false) => vec![],
+        })
+        .collect()
+}
+
+fn count(values: &[(Rational, u64)]) -> Result<Rational, &'static str> {
+    values
+        .iter()
+        .try_fold(0i64, |total, (_, count)| {
+            i64::try_from(*count)
+                .ok()
+                .and_then(|count| total.checked_add(count))
+        })
+        .map(Rational::from_integer)
+        .ok_or("Integer overflow")
+}
+
+fn sum(values: &[(Rational, u64)]) -> Result<Rational, &'static str> {
+    values
+        .iter()
+        .try_fold(Rational::from_integer(0), |total, (x, count)| {
+            i64::try_from(*count)
+                .ok()
+                .and_then(|count| x.checked_mul(Rational::from_integer(count)))
+                .and_then(|sum| total.checked_add(sum))
+        })
+        .ok_or("Integer overflow")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(number: i64) -> RollScalar {
+        RollScalar::Number(number.into())
+    }
+
+    fn roll(dice: &[i64]) -> RollScalar {
+        RollScalar::Roll(Some(dice.to_vec()))
+    }
+
+    #[test]
+    fn min_and_max_compare_totals() {
+        let functions = FunctionRegistry::new();
+
+        assert_eq!(functions.call("max", &[roll(&[3, 4]), n(10)]), Ok(n(10)));
+        assert_eq!(functions.call("MIN", &[roll(&[6, 6, 5]), n(15)]), Ok(n(15)));
+        assert_eq!(functions.call("max", &[]), Err("Wrong number of arguments"));
+    }
+
+    #[test]
+    fn count_sum_and_avg_look_at_every_value() {
+        let functions = FunctionRegistry::new();
+
+        assert_eq!(functions.call("count", &[roll(&[8, 9, 10])]), Ok(n(3)));
+        assert_eq!(functions.call("count", &[RollScalar::Roll(None)]), Ok(n(0)));
+        assert_eq!(functions.call("sum", &[roll(&[1, 2]), n(3)]), Ok(n(6)));
+        assert_eq!(
+            functions.call("avg", &[roll(&[1, 2, 6])]),
+            Ok(RollScalar::Number(Rational::new(3, 1).unwrap()))
+        );
+        assert_eq!(
+            functions.call("avg", &[roll(&[1, 2])]),
+            Ok(RollScalar::Number(Rational::new(3, 2).unwrap()))
+        );
+        assert_eq!(
+            functions.call("avg", &[RollScalar::Roll(None)]),
+            Err("Nothing to average")
+        );
+    }
+
+    #[test]
+    fn single_argument_functions() {
+        let functions = FunctionRegistry::new();
+        let seven_halves = || RollScalar::Number(Rational::new(-7, 2).unwrap());
+
+        assert_eq!(functions.call("abs", &[n(-4)]), Ok(n(4)));
+        assert_eq!(
+            functions.call("abs", &[n(i64::MIN)]),
+            Err("Integer overflow")
+        );
+        assert_eq!(functions.call("floor", &[seven_halves()]), Ok(n(-4)));
+        assert_eq!(functions.call("ceil", &[seven_halves()]), Ok(n(-3)));
+        assert_eq!(functions.call("round", &[seven_halves()]), Ok(n(-4)));
+        assert_eq!(
+            functions.call("abs", &[n(1), n(2)]),
+            Err("Wrong number of arguments")
+        );
+    }
+
+    #[test]
+    fn registered_functions_can_be_called() {
+        let mut functions = FunctionRegistry::empty();
+        assert_eq!(functions.call("max", &[n(1)]), Err("Unknown function"));
+
+        functions.register("Double", |args| {
+            roll_scalar_to_number(single_argument(args)?)?
+                .checked_mul(2.into())
+                .map(RollScalar::Number)
+                .ok_or("Integer overflow")
+        });
+        assert!(functions.contains("double"));
+        assert_eq!(functions.call("double", &[roll(&[2, 3])]), Ok(n(10)));
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+/// Bounds on how much work a roll is allowed to do, so that rolls like
+/// `99999999d99999999` fail with an error instead of exhausting memory or time.
+///
+/// ```
+/// use diceroll::calculation::limits::EvalLimits;
+///
+/// let limits = EvalLimits {
+///     max_dice_per_roll: 100,
+///     ..EvalLimits::default()
+/// };
+/// assert_eq!(limits.max_faces, EvalLimits::default().max_faces);
+/// ```
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct EvalLimits {
+    /// The most dice a single term like `8d10` can roll
+    pub max_dice_per_roll: u64,
+    /// The most dice a single term can roll when there are enough of them to be
+    /// counted per face instead of kept one by one
+    pub max_pooled_dice: u64,
+    /// The most dice pooled in total, counting explosions. Pooled dice are
+    /// counted here instead of in `max_total_dice`.
+    pub max_total_pooled_dice: u64,
+    /// The most dice rolled in total, counting rerolls and explosions
+    pub max_total_dice: u64,
+    /// The most faces a die can have
+    pub max_faces: u64,
+    /// The most times a single die can explode or compound in a row
+    pub max_explosion_depth: u64,
+    /// How deeply parentheses, function calls and other expressions can be nested
+    pub max_expression_depth: usize,
+    /// The most values a result can hold
+    pub max_output_size: usize,
+}
+
+impl Default for EvalLimits {
+    fn default() -> EvalLimits {
+        EvalLimits {
+            max_dice_per_roll: 10_000,
+            max_pooled_dice: 1_000_000_000,
+            max_total_pooled_dice: 10_000_000_000,
+            max_total_dice: 100_000,
+            max_faces: 1_000_000,
+            max_explosion_depth: 100,
+            max_expression_depth: 100,
+            max_output_size: 10_000,
+        }
+    }
+}
+
+/// The limit that a roll went over, along with the value of that limit
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum LimitExceeded {
+    DicePerRoll { limit: u64, requested: u64 },
+    TotalDice { limit: u64 },
+    TotalPooledDice { limit: u64 },
+    Faces { limit: u64, requested: u64 },
+    ExplosionDepth { limit: u64 },
+    ExpressionDepth { limit: usize },
+    OutputSize { limit: usize, size: usize },
+}
+
+impl LimitExceeded {
+    pub fn message(&self) -> &'static str {
+        match self {
+            LimitExceeded::DicePerRoll { .. } => "Too many dice in one roll",
+            LimitExceeded::TotalDice { .. } => "Too many dice rolled",
+            LimitExceeded::TotalPooledDice { .. } => "Too many dice pooled",
+            LimitExceeded::Faces { .. } => "Too many faces on a die",
+            LimitExceeded::ExplosionDepth { .. } => "Too many explosions in a row",
+            LimitExceeded::ExpressionDepth { .. } => "Expression is nested too deeply",
+            LimitExceeded::OutputSize { .. } => "Result is too large",
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::DicePerRoll { limit, requested } => write!(
+                f,
+                "{}: {} requested, the limit is {}",
+                self.message(),
+                requested,
+                limit
+            ),
+            LimitExceeded::Faces { limit, requested } => write!(
+                f,
+                "{}: {} requested, the limit is {}",
+                self.message(),
+                requested,
+                limit
+            ),
+            LimitExceeded::OutputSize { limit, size } => write!(
+                f,
+                "{}: {} values, the limit is {}",
+                self.message(),
+                size,
+                limit
+            ),
+            LimitExceeded::TotalDice { limit }
+            | LimitExceeded::TotalPooledDice { limit }
+            | LimitExceeded::ExplosionDepth { limit } => {
+                write!(f, "{}: the limit is {}", self.message(), limit)
+            }
+            LimitExceeded::ExpressionDepth { limit } => {
+                write!(f, "{}: the limit is {}", self.message(), limit)
+            }
+        }
+    }
+}
@@ -1,61 +1,213 @@
 use super::{
-    Operation, Operation::Add, Operation::Div, Operation::Mul, Operation::Sub, RollScalar,
-    RollScalar::Number, RollScalar::Roll, TwoScalarCalculation,
+    Operation, Operation::*, Rational, RollScalar, RollScalar::Bool, RollScalar::Number,
+    RollScalar::Pool, RollScalar::Roll, Rounding, TwoScalarCalculation,
 };
 
-fn scalar_calculate(scalar_calc: &TwoScalarCalculation) -> f64 {
-    simple_calculate(
-        &scalar_calc.first,
-        &scalar_calc.second,
-        scalar_calc.operation,
-    )
+/// Calculate the result of the operation, counting a roll as the sum of its values.
+///
+/// Comparing a roll with a number, on either side, compares each die in the
+/// roll and keeps the ones that pass, so `8d10 >= 8` and `8 <= 8d10` are both
+/// the roll of the dice that came up 8 or more. Any other comparison compares
+/// totals and gives a boolean, so a roll is compared as a total by adding to
+/// it or wrapping it in `sum`, as in `1d20 + 0 >= 15`.
+///
+/// `Div` rounds down, while `ExactDiv` keeps the exact fraction. Dividing by
+/// zero is an error, and so is a result that doesn't fit in an `i64`.
+pub fn scalar_calculate(scalar_calc: &TwoScalarCalculation) -> Result<RollScalar, &'static str> {
+    match scalar_calc.operation {
+        Add | Mul | Div | ExactDiv | Mod | Sub => Ok(Number(simple_calculate(
+            &scalar_calc.first,
+            &scalar_calc.second,
+            scalar_calc.operation,
+        )?)),
+        op => boolean_calculate(&scalar_calc.first, &scalar_calc.second, op),
+    }
+}
+
+/// How far `first` is ahead of `second`, negative when it's behind
+pub fn scalar_margin(first: &RollScalar, second: &RollScalar) -> Result<Rational, &'static str> {
+    checked(roll_scalar_to_number(first)?.checked_sub(roll_scalar_to_number(second)?))
+}
+
+/// Round the total of the scalar to an integer
+pub fn scalar_round(
+    roll_scalar: &RollScalar,
+    rounding: Rounding,
+) -> Result<RollScalar, &'static str> {
+    let number = roll_scalar_to_number(roll_scalar)?;
+    Ok(Number(match rounding {
+        Rounding::Floor => number.floor(),
+        Rounding::Ceil => number.ceil(),
+        Rounding::Round => number.round(),
+    }))
+}
+
+/// Negate a number, or every die of a roll. A boolean counts as 1 or 0.
+pub fn scalar_negate(roll_scalar: &RollScalar) -> Result<RollScalar, &'static str> {
+    match roll_scalar {
+        Roll(rolls) => Ok(Roll(match rolls {
+            Some(r) => Some(
+                r.iter()
+                    .map(|x| x.checked_neg().ok_or("Integer overflow"))
+                    .collect::<Result<Vec<i64>, &'static str>>()?,
+            ),
+            None => None,
+        })),
+        Pool(pool) => pool.checked_neg().map(Pool).ok_or("Integer overflow"),
+        _ => Ok(Number(checked(
+            roll_scalar_to_number(roll_scalar)?.checked_neg(),
+        )?)),
+    }
 }
 
-fn roll_scalar_to_float(roll_scalar: &RollScalar) -> f64 {
+/// A boolean counts as 1 or 0. It's an error when the total of a roll doesn't
+/// fit in an `i64`.
+pub fn roll_scalar_to_number(roll_scalar: &RollScalar) -> Result<Rational, &'static str> {
     match roll_scalar {
-        Number(num) => *num,
+        Number(num) => Ok(*num),
         Roll(rolls) => rolls
-            .as_ref()
-            .map_or(0f64, |r| r.iter().fold(0f64, |sum, x| sum + *x as f64)),
+            .iter()
+            .flatten()
+            .try_fold(0i64, |sum, x| sum.checked_add(*x))
+            .map(Rational::from_integer)
+            .ok_or("Integer overflow"),
+        Pool(pool) => pool
+            .total()
+            .map(Rational::from_integer)
+            .ok_or("Integer overflow"),
+        Bool(b) => Ok(Rational::from_integer(*b as i64)),
+    }
+}
+
+/// A number is true when it isn't 0, and a roll when it has any dice left
+pub fn roll_scalar_to_bool(roll_scalar: &RollScalar) -> bool {
+    match roll_scalar {
+        Number(num) => !num.is_zero(),
+        Roll(rolls) => rolls.as_ref().is_some_and(|r| !r.is_empty()),
+        Pool(pool) => !pool.is_empty(),
+        Bool(b) => *b,
     }
 }
 
-fn simple_calculate(first: &RollScalar, second: &RollScalar, op: Operation) -> f64 {
-    let first_scalar = roll_scalar_to_float(first);
-    let second_scalar = roll_scalar_to_float(second);
+fn simple_calculate(
+    first: &RollScalar,
+    second: &RollScalar,
+    op: Operation,
+) -> Result<Rational, &'static str> {
+    let first_scalar = roll_scalar_to_number(first)?;
+    let second_scalar = roll_scalar_to_number(second)?;
+    if matches!(op, Div | ExactDiv | Mod) && second_scalar.is_zero() {
+        return Err("Division by zero");
+    }
     match op {
-        Add => first_scalar + second_scalar,
-        Mul => first_scalar * second_scalar,
-        Div => first_scalar / second_scalar,
-        Sub => first_scalar - second_scalar,
+        Add => checked(first_scalar.checked_add(second_scalar)),
+        Mul => checked(first_scalar.checked_mul(second_scalar)),
+        Div => checked(first_scalar.checked_floor_div(second_scalar)),
+        ExactDiv => checked(first_scalar.checked_div(second_scalar)),
+        Mod => checked(first_scalar.checked_rem(second_scalar)),
+        Sub => checked(first_scalar.checked_sub(second_scalar)),
+        _ => roll_scalar_to_number(&boolean_calculate(first, second, op)?),
     }
 }
 
-#[cfg(test)]
+fn boolean_calculate(
+    first: &RollScalar,
+    second: &RollScalar,
+    op: Operation,
+) -> Result<RollScalar, &'static str> {
+    Ok(match (op, first, second) {
+        (And, _, _) => Bool(roll_scalar_to_bool(first) && roll_scalar_to_bool(second)),
+        (Or, _, _) => Bool(roll_scalar_to_bool(first) || roll_scalar_to_bool(second)),
+        (_, Roll(rolls), Number(target)) => Roll(
+            rolls
+                .as_ref()
+                .map(|r| {
+                    r.iter()
+                        .copied()
+                        .filter(|x| compare(Rational::from_integer(*x), *target, op))
+                        .collect::<Vec<i64>>()
+                })
+                .filter(|r| !r.is_empty()),
+        ),
+        (_, Pool(pool), Number(target)) => {
+            let mut pool = pool.clone();
+            pool.retain(|x| compare(Rational::from_integer(x), *target, op));
+            Pool(pool)
+        }
+        (_, Number(target), Roll(rolls)) => Roll(
+            rolls
+                .as_ref()
+                .map(|r| {
+                    r.iter()
+                        .copied()
+                        .filter(|x| compare(*target, Rational::from_integer(*x), op))
+                        .collect::<Vec<i64>>()
+                })
+                .filter(|r| !r.is_empty()),
+        ),
+        (_, Number(target), Pool(pool)) => {
+            let mut pool = pool.clone();
+            pool.retain(|x| compare(*target, Rational::from_integer(x), op));
+            Pool(pool)
+        }
+        _ => Bool(compare(
+            roll_scalar_to_number(first)?,
+            roll_scalar_to_number(second)?,
+            op,
+        )),
+    })
+}
 
+fn compare(first: Rational, second: Rational, op: Operation) -> bool {
+    match op {
+        Eq => first == second,
+        Ne => first != second,
+        Lt => first < second,
+        Le => first <= second,
+        Gt => first > second,
+        Ge => first >= second,
+        _ => false,
+    }
+}
+
+fn checked(result: Option<Rational>) -> Result<Rational, &'static str> {
+    result.ok_or("Integer overflow")
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
+    fn n(number: i64) -> RollScalar {
+        Number(number.into())
+    }
+
     #[test]
     fn simple_scalar_calculation_works_as_expected() {
-        let n5: RollScalar = Number(5.0);
-        let n9: RollScalar = Number(9.0);
+        let n5: RollScalar = n(5);
+        let n9: RollScalar = n(9);
         let v10: RollScalar = Roll(Some(vec![1, 2, 3, 4]));
         let v26: RollScalar = Roll(Some(vec![5, 6, 7, 8]));
         let v_none: RollScalar = Roll(None);
 
-        assert_eq!(simple_calculate(&n5, &n9, Add), 14.0);
-        assert_eq!(simple_calculate(&n5, &v10, Add), 15.0);
-        assert_eq!(simple_calculate(&v26, &n9, Add), 35.0);
-        assert_eq!(simple_calculate(&v26, &v10, Add), 36.0);
-        assert_eq!(simple_calculate(&v_none, &v10, Add), 10.0);
-        assert_eq!(simple_calculate(&n5, &v_none, Add), 5.0);
-        assert_eq!(simple_calculate(&v_none, &n9, Add), 9.0);
-        assert_eq!(simple_calculate(&v26, &v_none, Add), 26.0);
+        assert_eq!(simple_calculate(&n5, &n9, Add), Ok(14.into()));
+        assert_eq!(simple_calculate(&n5, &v10, Add), Ok(15.into()));
+        assert_eq!(simple_calculate(&v26, &n9, Add), Ok(35.into()));
+        assert_eq!(simple_calculate(&v26, &v10, Add), Ok(36.into()));
+        assert_eq!(simple_calculate(&v_none, &v10, Add), Ok(10.into()));
+        assert_eq!(simple_calculate(&n5, &v_none, Add), Ok(5.into()));
+        assert_eq!(simple_calculate(&v_none, &n9, Add), Ok(9.into()));
+        assert_eq!(simple_calculate(&v26, &v_none, Add), Ok(26.into()));
 
-        assert_eq!(simple_calculate(&n5, &n9, Mul), 45.0);
-        assert_eq!(simple_calculate(&n5, &n9, Div), 5.0 / 9.0);
-        assert_eq!(simple_calculate(&n5, &n9, Sub), -4.0);
+        assert_eq!(simple_calculate(&n5, &n9, Mul), Ok(45.into()));
+        assert_eq!(simple_calculate(&n5, &n9, Div), Ok(0.into()));
+        assert_eq!(simple_calculate(&n9, &n5, Div), Ok(1.into()));
+        assert_eq!(
+            simple_calculate(&n5, &n9, ExactDiv),
+            Ok(Rational::new(5, 9).unwrap())
+        );
+        assert_eq!(simple_calculate(&n9, &n5, Mod), Ok(4.into()));
+        assert_eq!(simple_calculate(&n5, &n9, Sub), Ok((-4).into()));
     }
 
     #[test]
@@ -63,9 +215,177 @@ mod tests {
         let tsc = TwoScalarCalculation {
             operation: Sub,
             first: Roll(Some(vec![1, 2, 3, 4])),
-            second: Number(9.0),
+            second: n(9),
+        };
+
+        assert_eq!(scalar_calculate(&tsc), Ok(n(1)));
+    }
+
+    #[test]
+    fn comparing_totals_gives_a_boolean() {
+        let calculate = |first, operation, second| {
+            scalar_calculate(&TwoScalarCalculation {
+                operation,
+                first,
+                second,
+            })
+            .unwrap()
+        };
+
+        assert_eq!(calculate(n(15), Ge, n(15)), Bool(true));
+        assert_eq!(calculate(n(14), Gt, n(15)), Bool(false));
+        assert_eq!(
+            calculate(Roll(Some(vec![3, 4])), Eq, Roll(Some(vec![7]))),
+            Bool(true)
+        );
+        assert_eq!(calculate(Roll(Some(vec![2])), Ne, Roll(None)), Bool(true));
+        assert_eq!(calculate(Bool(true), Lt, n(2)), Bool(true));
+        assert_eq!(calculate(n(2), Le, n(1)), Bool(false));
+    }
+
+    #[test]
+    fn comparing_a_roll_with_a_number_keeps_passing_dice() {
+        let comparison = |operation| {
+            scalar_calculate(&TwoScalarCalculation {
+                operation,
+                first: Roll(Some(vec![2, 8, 9, 10])),
+                second: n(8),
+            })
+            .unwrap()
+        };
+
+        assert_eq!(comparison(Ge), Roll(Some(vec![8, 9, 10])));
+        assert_eq!(comparison(Lt), Roll(Some(vec![2])));
+        assert_eq!(comparison(Eq), Roll(Some(vec![8])));
+
+        // The number can go on either side
+        assert_eq!(
+            scalar_calculate(&TwoScalarCalculation {
+                operation: Le,
+                first: n(8),
+                second: Roll(Some(vec![2, 8, 9, 10])),
+            }),
+            Ok(Roll(Some(vec![8, 9, 10])))
+        );
+        assert_eq!(
+            scalar_calculate(&TwoScalarCalculation {
+                operation: Gt,
+                first: Roll(Some(vec![2, 3])),
+                second: n(8),
+            }),
+            Ok(Roll(None))
+        );
+    }
+
+    #[test]
+    fn logical_operations_use_truthiness() {
+        let calculate = |first, operation, second| {
+            scalar_calculate(&TwoScalarCalculation {
+                operation,
+                first,
+                second,
+            })
+            .unwrap()
+        };
+
+        assert_eq!(calculate(n(1), And, Bool(true)), Bool(true));
+        assert_eq!(calculate(n(0), And, Bool(true)), Bool(false));
+        assert_eq!(calculate(Roll(None), Or, Bool(false)), Bool(false));
+        assert_eq!(calculate(Roll(Some(vec![1])), Or, Bool(false)), Bool(true));
+        assert_eq!(simple_calculate(&Bool(true), &n(0), Or), Ok(1.into()));
+    }
+
+    #[test]
+    fn scalar_margin_subtracts_totals() {
+        assert_eq!(scalar_margin(&Roll(Some(vec![6, 4])), &n(3)), Ok(7.into()));
+        assert_eq!(scalar_margin(&n(3), &Roll(None)), Ok(3.into()));
+        assert_eq!(scalar_margin(&n(3), &n(5)), Ok((-2).into()));
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        for operation in [Div, ExactDiv, Mod].iter() {
+            assert_eq!(
+                scalar_calculate(&TwoScalarCalculation {
+                    operation: *operation,
+                    first: n(3),
+                    second: Roll(None),
+                }),
+                Err("Division by zero")
+            );
+        }
+    }
+
+    #[test]
+    fn scalar_round_rounds_totals() {
+        let seven_halves = Number(Rational::new(7, 2).unwrap());
+
+        assert_eq!(scalar_round(&seven_halves, Rounding::Floor), Ok(n(3)));
+        assert_eq!(scalar_round(&seven_halves, Rounding::Ceil), Ok(n(4)));
+        assert_eq!(scalar_round(&seven_halves, Rounding::Round), Ok(n(4)));
+        assert_eq!(
+            scalar_round(&Roll(Some(vec![2, 3])), Rounding::Floor),
+            Ok(n(5))
+        );
+    }
+
+    #[test]
+    fn negative_values_keep_their_sign() {
+        let calculate = |first, operation, second| {
+            scalar_calculate(&TwoScalarCalculation {
+                operation,
+                first,
+                second,
+            })
+        };
+
+        assert_eq!(calculate(Roll(Some(vec![3])), Sub, n(5)), Ok(n(-2)));
+        assert_eq!(calculate(n(-7), Div, n(2)), Ok(n(-4)));
+        assert_eq!(calculate(n(-7), Mod, n(2)), Ok(n(1)));
+        assert_eq!(calculate(n(7), Mod, n(-2)), Ok(n(-1)));
+        assert_eq!(calculate(n(-3), Mul, n(-3)), Ok(n(9)));
+        assert_eq!(
+            calculate(Roll(Some(vec![-1, 0, 1])), Lt, n(0)),
+            Ok(Roll(Some(vec![-1])))
+        );
+        assert_eq!(
+            roll_scalar_to_number(&Roll(Some(vec![-4, 1]))),
+            Ok((-3).into())
+        );
+    }
+
+    #[test]
+    fn negating_keeps_the_shape_of_a_roll() {
+        assert_eq!(
+            scalar_negate(&Roll(Some(vec![1, 4]))),
+            Ok(Roll(Some(vec![-1, -4])))
+        );
+        assert_eq!(scalar_negate(&Roll(None)), Ok(Roll(None)));
+        assert_eq!(scalar_negate(&n(-3)), Ok(n(3)));
+        assert_eq!(scalar_negate(&Bool(true)), Ok(n(-1)));
+    }
+
+    #[test]
+    fn overflow_is_an_error() {
+        let calculate = |first, operation, second| {
+            scalar_calculate(&TwoScalarCalculation {
+                operation,
+                first,
+                second,
+            })
         };
 
-        assert_eq!(scalar_calculate(&tsc), 1.0);
+        assert_eq!(calculate(n(i64::MAX), Add, n(1)), Err("Integer overflow"));
+        assert_eq!(calculate(n(i64::MIN), Sub, n(1)), Err("Integer overflow"));
+        assert_eq!(calculate(n(i64::MIN), Div, n(-1)), Err("Integer overflow"));
+        assert_eq!(
+            calculate(Roll(Some(vec![i64::MAX, 1])), Add, n(0)),
+            Err("Integer overflow")
+        );
+        assert_eq!(
+            scalar_negate(&Roll(Some(vec![i64::MIN]))),
+            Err("Integer overflow")
+        );
+        assert_eq!(calculate(n(i64::MIN), Add, n(i64::MAX)), Ok(n(-1)));
     }
 }
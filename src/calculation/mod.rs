@@ -1,32 +1,97 @@
+pub mod audit;
+pub mod comparison;
 pub mod dice_rolls;
-
-// TODO: Remove after implementing math operations to dice rolls
-#[allow(dead_code)]
+pub mod die_source;
+pub mod face_counts;
+pub mod functions;
+pub mod limits;
 pub mod math_ops;
+pub mod outcomes;
+pub mod rational;
+pub mod rng;
+pub mod stateful_roll;
+pub mod symbol_dice;
+
+pub use functions::FunctionRegistry;
+pub use rational::Rational;
 
 #[derive(Debug, Clone)]
-pub struct RollRequest<R: rand::Rng> {
+pub struct RollRequest<R: die_source::DieSource> {
     rng: R,
-    result: Option<Vec<u64>>,
+    result: Option<Vec<i64>>,
+    /// The buffer of a dropped result, reused by the next roll
+    spare: Vec<i64>,
+    /// Large rolls keep their dice here instead of in `result`
+    pool: Option<face_counts::FaceCounts>,
+    order: SortOrder,
+    limits: limits::EvalLimits,
+    dice_rolled: u64,
+    dice_pooled: u64,
+    exceeded: Option<limits::LimitExceeded>,
+    /// Why the die source couldn't give a die, if it couldn't
+    failure: Option<&'static str>,
 }
 
-#[derive(Debug, Clone)]
-struct TwoScalarCalculation {
-    operation: Operation,
-    first: RollScalar,
-    second: RollScalar,
+/// A group of dice in a roll that all landed on the same face
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct DiceSet {
+    pub face: i64,
+    pub count: u64,
+}
+
+/// A Savage Worlds style trait roll, keeping the better of the trait dice and the wild die
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct WildDieRoll {
+    pub trait_roll: i64,
+    pub wild_roll: i64,
+    pub total: i64,
+    pub used_wild: bool,
+    pub critical_failure: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortOrder {
+    Ascending,
+    Descending,
 }
 
 #[derive(Debug, Clone)]
-enum RollScalar {
-    Number(f64),
-    Roll(Option<Vec<u64>>),
+pub struct TwoScalarCalculation {
+    pub operation: Operation,
+    pub first: RollScalar,
+    pub second: RollScalar,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum RollScalar {
+    Number(Rational),
+    Roll(Option<Vec<i64>>),
+    /// A roll with too many dice to keep one by one
+    Pool(face_counts::FaceCounts),
+    Bool(bool),
 }
 
 #[derive(Debug, Clone, Copy)]
-enum Operation {
+pub enum Operation {
     Add,
     Mul,
     Div,
+    ExactDiv,
+    Mod,
     Sub,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+    Round,
 }
@@ -0,0 +1,115 @@
+/// Turns the dice left in a roll into a named outcome
+pub trait OutcomeInterpreter {
+    type Outcome;
+
+    fn interpret(&self, dice: &[i64]) -> Self::Outcome;
+}
+
+/// Which value of a dice pool an `OutcomeTable` reads
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PoolReading {
+    Highest,
+    Lowest,
+    Total,
+}
+
+/// A named band of values, from `min` to `max` inclusive
+#[derive(PartialEq, Debug, Clone)]
+pub struct OutcomeBand {
+    pub name: String,
+    pub min: i64,
+    pub max: i64,
+}
+
+/// A user-definable table of outcome bands. Bands are checked in the order they
+/// were added, so the first band containing the value read from the pool wins.
+///
+/// ```
+/// use diceroll::calculation::outcomes::{OutcomeInterpreter, OutcomeTable, PoolReading};
+/// let table = OutcomeTable::new(PoolReading::Highest)
+///     .band(6, 6, "full success")
+///     .band(4, 5, "partial success")
+///     .band(1, 3, "failure");
+///
+/// assert_eq!(table.interpret(&[2, 5, 3]), Some("partial success".to_string()));
+/// assert_eq!(table.interpret(&[]), None);
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct OutcomeTable {
+    reading: PoolReading,
+    bands: Vec<OutcomeBand>,
+}
+
+impl OutcomeTable {
+    pub fn new(reading: PoolReading) -> OutcomeTable {
+        OutcomeTable {
+            reading,
+            bands: vec![],
+        }
+    }
+
+    /// Add a band covering the values from `min` to `max` inclusive
+    pub fn band(mut self, min: i64, max: i64, name: &str) -> OutcomeTable {
+        self.bands.push(OutcomeBand {
+            name: name.to_string(),
+            min,
+            max,
+        });
+        self
+    }
+
+    pub fn bands(&self) -> &[OutcomeBand] {
+        &self.bands
+    }
+
+    /// The value the table reads from the pool, if the pool isn't empty
+    pub fn read(&self, dice: &[i64]) -> Option<i64> {
+        match self.reading {
+            PoolReading::Highest => dice.iter().max().copied(),
+            PoolReading::Lowest => dice.iter().min().copied(),
+            PoolReading::Total if dice.is_empty() => None,
+            PoolReading::Total => Some(dice.iter().sum()),
+        }
+    }
+}
+
+impl OutcomeInterpreter for OutcomeTable {
+    type Outcome = Option<String>;
+
+    fn interpret(&self, dice: &[i64]) -> Option<String> {
+        let value = self.read(dice)?;
+        self.bands
+            .iter()
+            .find(|band| band.min <= value && value <= band.max)
+            .map(|band| band.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_reading_reads_the_right_value() {
+        let dice = [2, 6, 3];
+
+        assert_eq!(OutcomeTable::new(PoolReading::Highest).read(&dice), Some(6));
+        assert_eq!(OutcomeTable::new(PoolReading::Lowest).read(&dice), Some(2));
+        assert_eq!(OutcomeTable::new(PoolReading::Total).read(&dice), Some(11));
+        assert_eq!(OutcomeTable::new(PoolReading::Total).read(&[]), None);
+    }
+
+    #[test]
+    fn first_matching_band_wins() {
+        let table = OutcomeTable::new(PoolReading::Total)
+            .band(10, 10, "exactly ten")
+            .band(7, 12, "seven to twelve");
+
+        assert_eq!(table.interpret(&[4, 6]), Some("exactly ten".to_string()));
+        assert_eq!(
+            table.interpret(&[6, 6]),
+            Some("seven to twelve".to_string())
+        );
+        assert_eq!(table.interpret(&[1, 1]), None);
+    }
+}
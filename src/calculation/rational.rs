@@ -0,0 +1,267 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// An exact fraction, always stored in lowest terms with a positive denominator.
+/// Its arithmetic is all checked, since the fractions come from user entered
+/// expressions, and fails with `None` instead of panicking.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Rational {
+    /// `None` when `denominator` is 0 or the fraction doesn't fit in an `i64`
+    pub fn new(numerator: i64, denominator: i64) -> Option<Rational> {
+        if denominator == 0 {
+            None
+        } else {
+            Rational::reduce(numerator as i128, denominator as i128)
+        }
+    }
+
+    pub fn from_integer(integer: i64) -> Rational {
+        Rational {
+            numerator: integer,
+            denominator: 1,
+        }
+    }
+
+    pub fn numerator(&self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == 1
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    /// The largest integer less than or equal to the fraction
+    pub fn floor(&self) -> Rational {
+        Rational::from_integer(self.numerator.div_euclid(self.denominator))
+    }
+
+    /// The smallest integer greater than or equal to the fraction
+    pub fn ceil(&self) -> Rational {
+        let floor = self.floor();
+        if self.is_integer() {
+            floor
+        } else {
+            Rational::from_integer(floor.numerator + 1)
+        }
+    }
+
+    /// The nearest integer, rounding halves away from zero
+    pub fn round(&self) -> Rational {
+        let doubled = self.numerator as i128 * 2;
+        let denominator = self.denominator as i128 * 2;
+        let rounded = if doubled < 0 {
+            -((-doubled + self.denominator as i128).div_euclid(denominator))
+        } else {
+            (doubled + self.denominator as i128).div_euclid(denominator)
+        };
+        Rational::from_integer(rounded as i64)
+    }
+
+    /// `None` when the result doesn't fit, like `i64::MIN` negated
+    pub fn checked_neg(&self) -> Option<Rational> {
+        Rational::reduce(-(self.numerator as i128), self.denominator as i128)
+    }
+
+    pub fn checked_abs(&self) -> Option<Rational> {
+        if self.numerator < 0 {
+            self.checked_neg()
+        } else {
+            Some(*self)
+        }
+    }
+
+    pub fn checked_add(&self, other: Rational) -> Option<Rational> {
+        Rational::reduce(
+            self.numerator as i128 * other.denominator as i128
+                + other.numerator as i128 * self.denominator as i128,
+            self.denominator as i128 * other.denominator as i128,
+        )
+    }
+
+    pub fn checked_sub(&self, other: Rational) -> Option<Rational> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_mul(&self, other: Rational) -> Option<Rational> {
+        Rational::reduce(
+            self.numerator as i128 * other.numerator as i128,
+            self.denominator as i128 * other.denominator as i128,
+        )
+    }
+
+    /// Exact division, or `None` when dividing by zero or when the result doesn't fit
+    pub fn checked_div(&self, other: Rational) -> Option<Rational> {
+        if other.is_zero() {
+            None
+        } else {
+            Rational::reduce(
+                self.numerator as i128 * other.denominator as i128,
+                self.denominator as i128 * other.numerator as i128,
+            )
+        }
+    }
+
+    /// Division rounded down, or `None` when dividing by zero
+    pub fn checked_floor_div(&self, other: Rational) -> Option<Rational> {
+        self.checked_div(other).map(|quotient| quotient.floor())
+    }
+
+    /// The remainder of rounded down division, taking the sign of `other`, or
+    /// `None` when dividing by zero
+    pub fn checked_rem(&self, other: Rational) -> Option<Rational> {
+        let quotient = self.checked_floor_div(other)?;
+        self.checked_sub(other.checked_mul(quotient)?)
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Bring the fraction to lowest terms, or `None` when it doesn't fit in an `i64`
+    fn reduce(numerator: i128, denominator: i128) -> Option<Rational> {
+        let divisor = gcd(numerator, denominator).max(1) * denominator.signum();
+        Some(Rational {
+            numerator: i64::try_from(numerator / divisor).ok()?,
+            denominator: i64::try_from(denominator / divisor).ok()?,
+        })
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(integer: i64) -> Rational {
+        Rational::from_integer(integer)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (self.numerator as i128 * other.denominator as i128)
+            .cmp(&(other.numerator as i128 * self.denominator as i128))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Integers are shown as they are, other fractions as `numerator/denominator`
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fraction(numerator: i64, denominator: i64) -> Rational {
+        Rational::new(numerator, denominator).unwrap()
+    }
+
+    #[test]
+    fn rationals_are_stored_in_lowest_terms() {
+        assert_eq!(fraction(6, 4), fraction(3, 2));
+        assert_eq!(fraction(3, -6), fraction(-1, 2));
+        assert_eq!(fraction(0, -5), Rational::from_integer(0));
+        assert_eq!(fraction(-3, -6).denominator(), 2);
+        assert_eq!(Rational::new(1, 0), None);
+        assert_eq!(Rational::new(i64::MIN, -1), None);
+    }
+
+    #[test]
+    fn rounding_works_in_both_directions() {
+        let seven_halves = fraction(7, 2);
+        assert_eq!(seven_halves.floor(), 3.into());
+        assert_eq!(seven_halves.ceil(), 4.into());
+        assert_eq!(seven_halves.round(), 4.into());
+
+        let negative = seven_halves.checked_neg().unwrap();
+        assert_eq!(negative.floor(), (-4).into());
+        assert_eq!(negative.ceil(), (-3).into());
+        assert_eq!(negative.round(), (-4).into());
+
+        assert_eq!(fraction(10, 3).round(), 3.into());
+        assert_eq!(fraction(-1, 2).round(), (-1).into());
+        assert_eq!(fraction(-1, 3).round(), 0.into());
+        assert_eq!(fraction(-1, 3).ceil(), 0.into());
+        assert_eq!(Rational::from_integer(5).floor(), 5.into());
+    }
+
+    #[test]
+    fn division_rounds_down_or_stays_exact() {
+        let eleven = Rational::from_integer(11);
+        let two = Rational::from_integer(2);
+
+        assert_eq!(eleven.checked_floor_div(two), Some(5.into()));
+        assert_eq!(eleven.checked_div(two), Some(fraction(11, 2)));
+        assert_eq!(eleven.checked_rem(two), Some(1.into()));
+        assert_eq!(
+            eleven.checked_neg().unwrap().checked_floor_div(two),
+            Some((-6).into())
+        );
+        assert_eq!(
+            eleven.checked_neg().unwrap().checked_rem(two),
+            Some(1.into())
+        );
+        assert_eq!(eleven.checked_div(0.into()), None);
+        assert_eq!(eleven.checked_rem(0.into()), None);
+    }
+
+    #[test]
+    fn overflow_is_checked() {
+        let max = Rational::from_integer(i64::MAX);
+        let min = Rational::from_integer(i64::MIN);
+
+        assert_eq!(max.checked_add(1.into()), None);
+        assert_eq!(min.checked_sub(1.into()), None);
+        assert_eq!(max.checked_mul(2.into()), None);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(min.checked_abs(), None);
+        assert_eq!(min.checked_div((-1).into()), None);
+        assert_eq!(min.checked_add(max), Some((-1).into()));
+        assert_eq!(max.checked_neg(), Some(Rational::from_integer(-i64::MAX)));
+        assert_eq!(min.floor(), min);
+        assert_eq!(min.ceil(), min);
+        assert_eq!(min.round(), min);
+        assert_eq!(fraction(i64::MIN, 2).numerator(), i64::MIN / 2);
+    }
+
+    #[test]
+    fn rationals_are_ordered_and_displayed() {
+        assert!(fraction(1, 3) < fraction(1, 2));
+        assert!(fraction(-1, 2) < Rational::from_integer(0));
+        assert_eq!(fraction(7, 2).to_string(), "7/2");
+        assert_eq!(fraction(-8, 2).to_string(), "-4");
+    }
+}
@@ -0,0 +1,374 @@
+extern crate rand_chacha;
+extern crate rand_pcg;
+
+use super::die_source::{highest_face, DieSource};
+use rand::rngs::OsRng;
+use rand::{Error, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rand_pcg::Pcg64Mcg;
+use std::collections::BTreeMap;
+
+/// A random number generator picked at runtime. It's a `DieSource`, so it can
+/// be given to `RollRequest` and the rest of the crate like any other.
+pub type BoxedRng = Box<dyn DieSource>;
+
+/// Makes a generator out of the text after the `:` in a name like `chacha:42`,
+/// if there is any
+pub type RngConstructor = Box<dyn Fn(Option<&str>) -> Result<BoxedRng, &'static str> + Send + Sync>;
+
+struct RngBackend {
+    stable: bool,
+    constructor: RngConstructor,
+}
+
+/// The random number generators that can be picked by name. `new` starts with
+/// the built-in generators, and more can be registered on top of them.
+///
+/// A generator is picked with its name, optionally followed by a `:` and an
+/// argument, such as a seed in `chacha:42`.
+///
+/// ```
+/// use diceroll::calculation::rng::RngRegistry;
+/// use diceroll::controller::{CompiledRoll, RollResult};
+///
+/// let generators = RngRegistry::new();
+/// let roll: CompiledRoll = "3d6".parse().unwrap();
+/// let first = roll.roll(&mut generators.create("chacha:42").unwrap());
+/// let second = roll.roll(&mut generators.create("ChaCha:42").unwrap());
+/// assert_eq!(first, second);
+///
+/// assert_eq!(generators.create("nope").err(), Some("Unknown random number generator"));
+/// ```
+pub struct RngRegistry {
+    backends: BTreeMap<String, RngBackend>,
+}
+
+impl RngRegistry {
+    /// A registry with the built-in generators:
+    ///
+    /// * `thread`, rand's thread local generator, which is the default
+    /// * `chacha` or `chacha:<seed>`, ChaCha20. Seeded, it gives the same rolls
+    ///   on every platform.
+    /// * `pcg` or `pcg:<seed>`, the 64 bit PCG generator, which is fast but not
+    ///   cryptographically secure, for simulations
+    /// * `os`, the operating system's generator, for when nothing but fresh
+    ///   entropy will do
+    /// * `replay:<values>`, the recorded values separated by commas or spaces,
+    ///   given back in order. See `ReplayRng`.
+    ///
+    /// `chacha` and `pcg` without a seed are seeded from the operating system.
+    ///
+    /// Only seeded `chacha` and `pcg` and `replay` are reproducible: they give the
+    /// same numbers every time. The rolls made from those numbers stay the same
+    /// until the way dice are sampled from them changes, either in `rand` or in
+    /// this crate, such as the `POOL_THRESHOLD` above which dice are pooled and
+    /// how pools are sampled. `thread` and `os` are never reproducible.
+    pub fn new() -> RngRegistry {
+        let mut registry = RngRegistry::empty();
+        registry
+            .register("thread", false, |argument| {
+                no_argument(argument)?;
+                Ok(Box::new(rand::thread_rng()))
+            })
+            .register("chacha", true, |argument| seeded::<ChaCha20Rng>(argument))
+            .register("pcg", true, |argument| seeded::<Pcg64Mcg>(argument))
+            .register("os", false, |argument| {
+                no_argument(argument)?;
+                Ok(Box::new(OsRng))
+            })
+            .register("replay", true, |argument| {
+                Ok(Box::new(ReplayRng::parse(
+                    argument.ok_or("The replay generator needs a list of values")?,
+                )?))
+            });
+        registry
+    }
+
+    /// A registry without any generators
+    pub fn empty() -> RngRegistry {
+        RngRegistry {
+            backends: BTreeMap::new(),
+        }
+    }
+
+    /// Add a generator, replacing any generator with the same name. Names are
+    /// not case sensitive. A generator is `stable` when it gives the same
+    /// numbers for the same argument on every platform and version.
+    pub fn register<F>(&mut self, name: &str, stable: bool, constructor: F) -> &mut Self
+    where
+        F: Fn(Option<&str>) -> Result<BoxedRng, &'static str> + Send + Sync + 'static,
+    {
+        self.backends.insert(
+            name.to_lowercase(),
+            RngBackend {
+                stable,
+                constructor: Box::new(constructor),
+            },
+        );
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.backends.contains_key(&name.to_lowercase())
+    }
+
+    /// The names of the generators, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.backends.keys().map(String::as_str)
+    }
+
+    /// Whether the generator with this name, without its argument, gives the
+    /// same numbers on every platform and version
+    pub fn is_stable(&self, name: &str) -> bool {
+        self.backends
+            .get(&name.to_lowercase())
+            .is_some_and(|backend| backend.stable)
+    }
+
+    /// Make the generator picked by `spec`, a name optionally followed by a `:`
+    /// and an argument
+    pub fn create(&self, spec: &str) -> Result<BoxedRng, &'static str> {
+        let (name, argument) = match spec.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (spec, None),
+        };
+        match self.backends.get(&name.trim().to_lowercase()) {
+            Some(backend) => (backend.constructor)(argument),
+            None => Err("Unknown random number generator"),
+        }
+    }
+}
+
+impl Default for RngRegistry {
+    fn default() -> RngRegistry {
+        RngRegistry::new()
+    }
+}
+
+fn no_argument(argument: Option<&str>) -> Result<(), &'static str> {
+    match argument {
+        Some(_) => Err("This random number generator doesn't take a seed"),
+        None => Ok(()),
+    }
+}
+
+fn seeded<R: SeedableRng + RngCore + 'static>(
+    argument: Option<&str>,
+) -> Result<BoxedRng, &'static str> {
+    match argument {
+        Some(seed) => {
+            let seed = seed.trim().parse().map_err(|_| "Invalid seed")?;
+            Ok(Box::new(R::seed_from_u64(seed)))
+        }
+        None => Ok(Box::new(R::from_entropy())),
+    }
+}
+
+/// A generator that gives back a recorded list of 64 bit values in order, so
+/// that a session can be rolled again exactly as it happened. Dice are sampled
+/// from the values the same way they're sampled from any other generator, and
+/// each value is used once, whether 32 or 64 bits are asked for. Like
+/// `ManualDice`, it fails the roll once the values run out instead of making
+/// up more.
+///
+/// ```
+/// use diceroll::calculation::rng::ReplayRng;
+/// use diceroll::calculation::RollRequest;
+///
+/// let mut request = RollRequest::new(ReplayRng::new(vec![0, 1 << 63]).unwrap());
+/// assert_eq!(request.roll_dice(2, 6).as_slice(), Some(&[1, 4][..]));
+/// assert!(request.roll_dice(1, 6).as_slice().is_none());
+/// assert_eq!(request.failure(), Some("Ran out of replay values"));
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct ReplayRng {
+    values: Vec<u64>,
+    position: usize,
+}
+
+impl ReplayRng {
+    /// Replay the values, which can't be empty
+    pub fn new(values: Vec<u64>) -> Result<ReplayRng, &'static str> {
+        if values.is_empty() {
+            return Err("The replay generator needs a list of values");
+        }
+        Ok(ReplayRng {
+            values,
+            position: 0,
+        })
+    }
+
+    /// Replay values written out as numbers separated by commas or whitespace
+    pub fn parse(text: &str) -> Result<ReplayRng, &'static str> {
+        let values = text
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse().map_err(|_| "Invalid replay value"))
+            .collect::<Result<Vec<u64>, &'static str>>()?;
+        ReplayRng::new(values)
+    }
+
+    /// Whether every value has been used
+    pub fn exhausted(&self) -> bool {
+        self.position == self.values.len()
+    }
+}
+
+impl DieSource for ReplayRng {
+    fn roll_die(&mut self, dice_range: u64) -> Result<i64, &'static str> {
+        if dice_range == 0 {
+            return Err("A die needs at least one face");
+        }
+        let mut values = ReplayValues {
+            replay: self,
+            ran_out: false,
+        };
+        let die = values.gen_range(1, highest_face(dice_range) + 1);
+        match values.ran_out {
+            true => Err("Ran out of replay values"),
+            false => Ok(die),
+        }
+    }
+}
+
+/// The values of a replay as a generator, noting when a die needed more values
+/// than were left
+struct ReplayValues<'a> {
+    replay: &'a mut ReplayRng,
+    ran_out: bool,
+}
+
+impl RngCore for ReplayValues<'_> {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self.replay.values.get(self.replay.position) {
+            Some(value) => {
+                self.replay.position += 1;
+                *value
+            }
+            None => {
+                self.ran_out = true;
+                0
+            }
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::RollRequest;
+
+    fn roll(spec: &str) -> Option<Vec<i64>> {
+        roll_with(&RngRegistry::new(), spec)
+    }
+
+    fn roll_with(generators: &RngRegistry, spec: &str) -> Option<Vec<i64>> {
+        let mut request = RollRequest::new(generators.create(spec).unwrap());
+        request.roll_dice(10, 20);
+        request.as_vec()
+    }
+
+    #[test]
+    fn every_builtin_generator_rolls_dice() {
+        let generators = RngRegistry::new();
+        assert_eq!(
+            generators.names().collect::<Vec<&str>>(),
+            vec!["chacha", "os", "pcg", "replay", "thread"]
+        );
+        for spec in [
+            "thread",
+            "chacha",
+            "chacha:1",
+            "pcg",
+            "pcg:1",
+            "os",
+            "replay:1,2 3 4 5 6 7 8 9 10",
+        ] {
+            let dice = roll(spec).unwrap();
+            assert_eq!(dice.len(), 10);
+            assert!(dice.iter().all(|die| (1..=20).contains(die)), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn seeded_generators_are_reproducible() {
+        assert_eq!(roll("chacha:42"), roll("chacha: 42"));
+        assert_eq!(roll("PCG:42"), roll("pcg:42"));
+        assert_ne!(roll("chacha:42"), roll("chacha:43"));
+        assert_ne!(roll("chacha:42"), roll("pcg:42"));
+        // The rolls that stable generators give mustn't change between versions
+        assert_eq!(
+            roll("chacha:42"),
+            Some(vec![2, 3, 4, 4, 8, 9, 11, 13, 15, 17])
+        );
+        assert_eq!(
+            roll("pcg:42"),
+            Some(vec![2, 3, 5, 8, 8, 12, 12, 12, 16, 18])
+        );
+
+        let generators = RngRegistry::new();
+        assert!(generators.is_stable("chacha"));
+        assert!(generators.is_stable("Replay"));
+        assert!(!generators.is_stable("os"));
+        assert!(!generators.is_stable("nope"));
+    }
+
+    #[test]
+    fn generator_arguments_are_checked() {
+        let generators = RngRegistry::new();
+        assert_eq!(generators.create("chacha:x").err(), Some("Invalid seed"));
+        assert_eq!(
+            generators.create("os:1").err(),
+            Some("This random number generator doesn't take a seed")
+        );
+        assert_eq!(
+            generators.create("replay").err(),
+            Some("The replay generator needs a list of values")
+        );
+        assert_eq!(
+            generators.create("replay:1,x").err(),
+            Some("Invalid replay value")
+        );
+    }
+
+    #[test]
+    fn replays_fail_when_the_values_run_out() {
+        let mut request = RollRequest::new(ReplayRng::new(vec![0, 1 << 63]).unwrap());
+        assert_eq!(request.roll_dice(2, 20).as_slice(), Some(&[1, 11][..]));
+        assert!(request.roll_dice(1, 20).as_slice().is_none());
+        assert_eq!(request.failure(), Some("Ran out of replay values"));
+        assert_eq!(roll("replay:1,2,3"), None);
+
+        let mut replay = ReplayRng::new(vec![0]).unwrap();
+        assert!(!replay.exhausted());
+        assert_eq!(replay.roll_die(6), Ok(1));
+        assert!(replay.exhausted());
+        assert_eq!(replay.roll_die(6), Err("Ran out of replay values"));
+    }
+
+    #[test]
+    fn generators_can_be_registered() {
+        let mut generators = RngRegistry::empty();
+        generators.register("Zeros", true, |_| {
+            Ok(Box::new(rand::rngs::mock::StepRng::new(0, 0)))
+        });
+        assert!(generators.contains("zeros"));
+        assert_eq!(roll_with(&generators, "ZEROS"), Some(vec![1; 10]));
+    }
+}
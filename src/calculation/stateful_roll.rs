@@ -0,0 +1,172 @@
+extern crate rand;
+
+use super::die_source::DieSource;
+use super::limits::LimitExceeded;
+use super::RollRequest;
+
+/// A die in a stateful roll, labelled with the pool it was rolled in
+#[derive(PartialEq, Debug, Clone)]
+pub struct RolledDie {
+    pub value: i64,
+    pub dice_range: u64,
+    pub label: String,
+}
+
+/// A roll that keeps its dice around, so that some of them can be rerolled later
+/// while the rest keep their values. Every roll and reroll adds the state of
+/// the dice after it to the history. A roll or reroll that would go over a
+/// limit leaves the dice as they were, and `exceeded` says which limit.
+///
+/// ```
+/// use diceroll::calculation::stateful_roll::StatefulRoll;
+/// let mut roll = StatefulRoll::new(rand::thread_rng());
+///
+/// // Roll 3d6 and 2d8, then reroll every die below 3
+/// roll.roll(3, 6, "d6").roll(2, 8, "d8").reroll_where(|die| die.value < 3);
+///
+/// assert_eq!(roll.dice().len(), 5);
+/// assert_eq!(roll.history().len(), 3);
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatefulRoll<R: DieSource> {
+    request: RollRequest<R>,
+    dice: Vec<RolledDie>,
+    history: Vec<Vec<RolledDie>>,
+}
+
+impl<R: DieSource> StatefulRoll<R> {
+    pub fn new(rng: R) -> StatefulRoll<R> {
+        StatefulRoll {
+            request: RollRequest::new(rng),
+            dice: vec![],
+            history: vec![],
+        }
+    }
+
+    /// Add `number_of_dice` dice in `dice_range` to the roll, labelled with `label`
+    pub fn roll(
+        &mut self,
+        number_of_dice: u64,
+        dice_range: u64,
+        label: &str,
+    ) -> &mut StatefulRoll<R> {
+        if let Err(exceeded) = self.request.check_dice(number_of_dice, dice_range) {
+            self.request.exceed(exceeded);
+            return self;
+        }
+        for _ in 0..number_of_dice {
+            let value = self.request.roll_die(dice_range);
+            self.dice.push(RolledDie {
+                value,
+                dice_range,
+                label: label.to_string(),
+            });
+        }
+        self.history.push(self.dice.clone());
+        self
+    }
+
+    /// Reroll every die matching `predicate`, keeping the values of the others
+    pub fn reroll_where<F>(&mut self, predicate: F) -> &mut StatefulRoll<R>
+    where
+        F: Fn(&RolledDie) -> bool,
+    {
+        let rerolls = self.dice.iter().filter(|die| predicate(die)).count();
+        if let Err(exceeded) = self.request.check_total(rerolls as u64) {
+            self.request.exceed(exceeded);
+            return self;
+        }
+        for die in self.dice.iter_mut() {
+            if predicate(die) {
+                die.value = self.request.roll_die(die.dice_range);
+            }
+        }
+        self.history.push(self.dice.clone());
+        self
+    }
+
+    pub fn dice(&self) -> &[RolledDie] {
+        &self.dice
+    }
+
+    /// The dice with the given label
+    pub fn pool<'a>(&'a self, label: &'a str) -> impl Iterator<Item = &'a RolledDie> {
+        self.dice.iter().filter(move |die| die.label == label)
+    }
+
+    /// The first limit that a roll or reroll would have gone over, if any
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        self.request.exceeded()
+    }
+
+    /// Why the die source couldn't give a die, if it couldn't. The dice it
+    /// couldn't give are left as 1s.
+    pub fn failure(&self) -> Option<&'static str> {
+        self.request.failure()
+    }
+
+    /// The state of the dice after every roll and reroll, oldest first
+    pub fn history(&self) -> &[Vec<RolledDie>] {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reroll_only_changes_matching_dice() {
+        let mut roll = StatefulRoll::new(rand::rngs::mock::StepRng::new(2, 1));
+        roll.roll(2, 6, "base");
+        roll.dice[1].value = 6;
+
+        // StepRng always rolls a 1
+        roll.reroll_where(|die| die.value == 6);
+        let values: Vec<i64> = roll.dice().iter().map(|die| die.value).collect();
+        assert_eq!(values, vec![1, 1]);
+
+        roll.dice[0].value = 4;
+        roll.reroll_where(|die| die.value == 6);
+        let values: Vec<i64> = roll.dice().iter().map(|die| die.value).collect();
+        assert_eq!(values, vec![4, 1]);
+    }
+
+    #[test]
+    fn history_records_every_step() {
+        let mut roll = StatefulRoll::new(rand::rngs::mock::StepRng::new(2, 1));
+        roll.roll(1, 6, "base").roll(2, 10, "skill");
+
+        assert_eq!(roll.history().len(), 2);
+        assert_eq!(roll.history()[0].len(), 1);
+        assert_eq!(roll.history()[1].len(), 3);
+        assert_eq!(roll.pool("skill").count(), 2);
+        assert!(roll.pool("skill").all(|die| die.dice_range == 10));
+    }
+
+    #[test]
+    fn rolls_over_the_limits_leave_the_dice_alone() {
+        let mut roll = StatefulRoll::new(rand::thread_rng());
+        roll.roll(1 << 40, 6, "base");
+        assert!(roll.dice().is_empty());
+        assert!(roll.history().is_empty());
+        assert_eq!(
+            roll.exceeded(),
+            Some(LimitExceeded::DicePerRoll {
+                limit: 10_000,
+                requested: 1 << 40
+            })
+        );
+
+        let mut roll = StatefulRoll::new(rand::thread_rng());
+        for _ in 0..10 {
+            roll.roll(10_000, 6, "base");
+        }
+        roll.reroll_where(|_| true);
+        assert_eq!(roll.history().len(), 10);
+        assert_eq!(
+            roll.exceeded(),
+            Some(LimitExceeded::TotalDice { limit: 100_000 })
+        );
+    }
+}
@@ -0,0 +1,308 @@
+extern crate rand;
+
+use super::die_source::DieSource;
+use super::RollRequest;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Symbol {
+    Success,
+    Failure,
+    Advantage,
+    Threat,
+    Triumph,
+    Despair,
+    LightSide,
+    DarkSide,
+}
+
+use Symbol::{
+    Advantage as A, DarkSide as D, Despair as X, Failure as F, LightSide as L, Success as S,
+    Threat as T, Triumph as Tr,
+};
+
+/// A die whose faces show zero or more symbols instead of a number
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SymbolDie {
+    pub name: &'static str,
+    pub faces: &'static [&'static [Symbol]],
+}
+
+pub const BOOST: SymbolDie = SymbolDie {
+    name: "boost",
+    faces: &[&[], &[], &[S], &[S, A], &[A, A], &[A]],
+};
+
+pub const SETBACK: SymbolDie = SymbolDie {
+    name: "setback",
+    faces: &[&[], &[], &[F], &[F], &[T], &[T]],
+};
+
+pub const ABILITY: SymbolDie = SymbolDie {
+    name: "ability",
+    faces: &[&[], &[S], &[S], &[S, S], &[A], &[A], &[S, A], &[A, A]],
+};
+
+pub const DIFFICULTY: SymbolDie = SymbolDie {
+    name: "difficulty",
+    faces: &[&[], &[F], &[F, F], &[T], &[T], &[T], &[T, T], &[F, T]],
+};
+
+pub const PROFICIENCY: SymbolDie = SymbolDie {
+    name: "proficiency",
+    faces: &[
+        &[],
+        &[S],
+        &[S],
+        &[S, S],
+        &[S, S],
+        &[A],
+        &[S, A],
+        &[S, A],
+        &[S, A],
+        &[A, A],
+        &[A, A],
+        &[Tr],
+    ],
+};
+
+pub const CHALLENGE: SymbolDie = SymbolDie {
+    name: "challenge",
+    faces: &[
+        &[],
+        &[F],
+        &[F],
+        &[F, F],
+        &[F, F],
+        &[T],
+        &[T],
+        &[F, T],
+        &[F, T],
+        &[T, T],
+        &[T, T],
+        &[X],
+    ],
+};
+
+pub const FORCE: SymbolDie = SymbolDie {
+    name: "force",
+    faces: &[
+        &[D],
+        &[D],
+        &[D],
+        &[D],
+        &[D],
+        &[D],
+        &[D, D],
+        &[L],
+        &[L],
+        &[L, L],
+        &[L, L],
+        &[L, L],
+    ],
+};
+
+/// The face a single symbol die landed on
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SymbolFace {
+    pub die: &'static str,
+    pub symbols: &'static [Symbol],
+}
+
+/// The symbols left over once opposing symbols cancel each other out.
+///
+/// A positive `successes` is the number of uncancelled successes, a negative
+/// one the number of uncancelled failures, and likewise for `advantages` and
+/// threats. Triumphs and despairs count as a success and a failure when
+/// cancelling, but are always reported in full.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct SymbolSummary {
+    pub successes: i64,
+    pub advantages: i64,
+    pub triumphs: u64,
+    pub despairs: u64,
+    pub light_side: u64,
+    pub dark_side: u64,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SymbolRollResult {
+    pub faces: Vec<SymbolFace>,
+    pub summary: SymbolSummary,
+}
+
+impl SymbolSummary {
+    pub fn from_faces(faces: &[SymbolFace]) -> SymbolSummary {
+        let mut summary = SymbolSummary::default();
+        for symbol in faces.iter().flat_map(|face| face.symbols) {
+            match symbol {
+                Symbol::Success => summary.successes += 1,
+                Symbol::Failure => summary.successes -= 1,
+                Symbol::Advantage => summary.advantages += 1,
+                Symbol::Threat => summary.advantages -= 1,
+                Symbol::Triumph => {
+                    summary.successes += 1;
+                    summary.triumphs += 1;
+                }
+                Symbol::Despair => {
+                    summary.successes -= 1;
+                    summary.despairs += 1;
+                }
+                Symbol::LightSide => summary.light_side += 1,
+                Symbol::DarkSide => summary.dark_side += 1,
+            }
+        }
+        summary
+    }
+
+    /// A check succeeds when at least one success is left after cancelling
+    pub fn is_success(&self) -> bool {
+        self.successes > 0
+    }
+}
+
+impl<R: DieSource> RollRequest<R> {
+    /// Roll a pool of symbol dice, given as pairs of the number of dice and the die to roll.
+    /// There's no result when the pool goes over a limit, and `exceeded` says
+    /// which, or when the die source can't give a die, and `failure` says why.
+    ///
+    /// ```
+    /// use diceroll::calculation::RollRequest;
+    /// use diceroll::calculation::symbol_dice::{ABILITY, DIFFICULTY, PROFICIENCY};
+    /// let rng = rand::thread_rng();
+    /// let mut request = RollRequest::new(rng);
+    ///
+    /// // Roll 2 ability, 1 proficiency and 2 difficulty dice
+    /// let result = request.roll_symbol_dice(&[(2, ABILITY), (1, PROFICIENCY), (2, DIFFICULTY)]);
+    /// assert_eq!(result.map(|result| result.faces.len()), Some(5));
+    /// ```
+    pub fn roll_symbol_dice(&mut self, pool: &[(u64, SymbolDie)]) -> Option<SymbolRollResult> {
+        let mut faces = vec![];
+        for (number_of_dice, die) in pool {
+            if let Err(exceeded) = self.check_dice(*number_of_dice, die.faces.len() as u64) {
+                self.exceed(exceeded);
+                return None;
+            }
+            for _ in 0..*number_of_dice {
+                let face = self.roll_die(die.faces.len() as u64) as usize;
+                if self.failure().is_some() {
+                    return None;
+                }
+                faces.push(SymbolFace {
+                    die: die.name,
+                    symbols: die.faces[face - 1],
+                });
+            }
+        }
+        let summary = SymbolSummary::from_faces(&faces);
+        Some(SymbolRollResult { faces, summary })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::die_source::ManualDice;
+    use crate::calculation::limits::{EvalLimits, LimitExceeded};
+
+    fn face(symbols: &'static [Symbol]) -> SymbolFace {
+        SymbolFace {
+            die: "test",
+            symbols,
+        }
+    }
+
+    #[test]
+    fn opposing_symbols_cancel() {
+        let summary = SymbolSummary::from_faces(&[
+            face(&[S, S]),
+            face(&[F]),
+            face(&[A]),
+            face(&[T, T]),
+            face(&[L, D]),
+        ]);
+
+        assert_eq!(
+            summary,
+            SymbolSummary {
+                successes: 1,
+                advantages: -1,
+                triumphs: 0,
+                despairs: 0,
+                light_side: 1,
+                dark_side: 1,
+            }
+        );
+        assert!(summary.is_success());
+    }
+
+    #[test]
+    fn triumph_and_despair_count_as_success_and_failure() {
+        let summary = SymbolSummary::from_faces(&[face(&[Tr]), face(&[X]), face(&[F])]);
+
+        assert_eq!(summary.successes, -1);
+        assert_eq!(summary.triumphs, 1);
+        assert_eq!(summary.despairs, 1);
+        assert!(!summary.is_success());
+    }
+
+    #[test]
+    fn seeded_symbol_roll_is_reproducible() {
+        let rng = rand::rngs::mock::StepRng::new(2, 1);
+        let mut request = RollRequest::new(rng);
+
+        // StepRng always rolls the first face
+        let result = request
+            .roll_symbol_dice(&[(2, ABILITY), (1, FORCE)])
+            .unwrap();
+        assert_eq!(
+            result.faces,
+            vec![
+                SymbolFace {
+                    die: "ability",
+                    symbols: &[]
+                },
+                SymbolFace {
+                    die: "ability",
+                    symbols: &[]
+                },
+                SymbolFace {
+                    die: "force",
+                    symbols: &[D]
+                },
+            ]
+        );
+        assert_eq!(result.summary.dark_side, 1);
+    }
+
+    #[test]
+    fn failed_dice_leave_no_result() {
+        let mut request = RollRequest::new(ManualDice::new(vec![1, 2]));
+
+        assert_eq!(request.roll_symbol_dice(&[(3, BOOST)]), None);
+        assert_eq!(request.failure(), Some("Ran out of dice"));
+    }
+
+    #[test]
+    fn pools_are_held_to_the_limits() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        assert_eq!(request.roll_symbol_dice(&[(u64::MAX, BOOST)]), None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::DicePerRoll {
+                limit: 10_000,
+                requested: u64::MAX
+            })
+        );
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.with_limits(EvalLimits {
+            max_total_dice: 5,
+            ..EvalLimits::default()
+        });
+        assert_eq!(request.roll_symbol_dice(&[(3, BOOST), (3, SETBACK)]), None);
+        assert_eq!(
+            request.exceeded(),
+            Some(LimitExceeded::TotalDice { limit: 5 })
+        );
+    }
+}
@@ -0,0 +1,274 @@
+extern crate rand_chacha;
+
+use super::{Evaluation, RollError};
+use crate::calculation::limits::EvalLimits;
+use crate::calculation::math_ops::roll_scalar_to_number;
+use crate::calculation::{FunctionRegistry, Rational};
+use crate::parsing::Expression;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// How many rolls share a random number stream. Each chunk of rolls gets its own
+/// stream, so the rolls don't depend on which thread happens to roll them.
+pub const CHUNK_SIZE: u64 = 4096;
+
+/// How many times an expression was rolled and what it came to. Booleans are
+/// counted as 1 or 0, so the mean of a comparison is how often it passed.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct BatchStats {
+    pub iterations: u64,
+    /// How many rolls came to each total
+    pub totals: BTreeMap<Rational, u64>,
+}
+
+impl BatchStats {
+    /// Add the rolls of another batch to this one
+    pub fn merge(&mut self, other: &BatchStats) {
+        self.iterations += other.iterations;
+        for (total, count) in &other.totals {
+            *self.totals.entry(*total).or_insert(0) += count;
+        }
+    }
+
+    pub fn min(&self) -> Option<Rational> {
+        self.totals.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<Rational> {
+        self.totals.keys().next_back().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.iterations == 0 {
+            return None;
+        }
+        let sum: f64 = self
+            .totals
+            .iter()
+            .map(|(total, count)| total.to_f64() * *count as f64)
+            .sum();
+        Some(sum / self.iterations as f64)
+    }
+
+    pub fn std_dev(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let squares: f64 = self
+            .totals
+            .iter()
+            .map(|(total, count)| (total.to_f64() - mean).powi(2) * *count as f64)
+            .sum();
+        Some((squares / self.iterations as f64).sqrt())
+    }
+
+    /// The share of rolls that came to `total` or more
+    pub fn at_least(&self, total: Rational) -> Option<f64> {
+        if self.iterations == 0 {
+            return None;
+        }
+        let count: u64 = self.totals.range(total..).map(|(_, count)| count).sum();
+        Some(count as f64 / self.iterations as f64)
+    }
+}
+
+/// Roll the expression `iterations` times on `threads` threads, or on as many
+/// threads as there are cores when `threads` is 0. The rolls come from ChaCha
+/// streams seeded with `seed`, so the same seed gives the same statistics no
+/// matter how many threads are used.
+///
+/// When rolls fail, the error of the first chunk of rolls to fail is returned.
+///
+/// ```
+/// use diceroll::calculation::limits::EvalLimits;
+/// use diceroll::calculation::FunctionRegistry;
+/// use diceroll::controller::batch::roll_batch;
+/// use diceroll::parsing::math_ops::expression_parse;
+///
+/// let (_, expression) = expression_parse("1d20 + 5 >= 15").unwrap();
+/// let functions = FunctionRegistry::new();
+/// let limits = EvalLimits::default();
+/// let stats = roll_batch(&expression, 100_000, 42, 4, &functions, &limits).unwrap();
+///
+/// assert_eq!(stats.iterations, 100_000);
+/// assert!((stats.mean().unwrap() - 0.55).abs() < 0.01);
+/// assert_eq!(
+///     roll_batch(&expression, 100_000, 42, 1, &functions, &limits),
+///     Ok(stats)
+/// );
+/// ```
+pub fn roll_batch(
+    expression: &Expression,
+    iterations: u64,
+    seed: u64,
+    threads: usize,
+    functions: &FunctionRegistry,
+    limits: &EvalLimits,
+) -> Result<BatchStats, RollError> {
+    let chunks = iterations.div_ceil(CHUNK_SIZE);
+    let threads = match threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    }
+    .min(chunks.max(1) as usize);
+
+    let next_chunk = AtomicU64::new(0);
+    let results: Mutex<Vec<(u64, Result<BatchStats, RollError>)>> = Mutex::new(vec![]);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= chunks {
+                    break;
+                }
+                let rolls = CHUNK_SIZE.min(iterations - chunk * CHUNK_SIZE);
+                let result = roll_chunk(expression, rolls, seed, chunk, functions, limits);
+                if let Ok(mut results) = results.lock() {
+                    results.push((chunk, result));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap_or_default();
+    results.sort_unstable_by_key(|(chunk, _)| *chunk);
+    let mut stats = BatchStats::default();
+    for (_, result) in results {
+        stats.merge(&result?);
+    }
+    Ok(stats)
+}
+
+fn roll_chunk(
+    expression: &Expression,
+    rolls: u64,
+    seed: u64,
+    chunk: u64,
+    functions: &FunctionRegistry,
+    limits: &EvalLimits,
+) -> Result<BatchStats, RollError> {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    rng.set_stream(chunk);
+    let mut stats = BatchStats::default();
+    for _ in 0..rolls {
+        let scalar = Evaluation::new(&mut rng, functions, *limits).evaluate(expression)?;
+        let total = roll_scalar_to_number(&scalar)?;
+        stats.iterations += 1;
+        *stats.totals.entry(total).or_insert(0) += 1;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::math_ops::expression_parse;
+
+    fn batch(
+        text: &str,
+        iterations: u64,
+        seed: u64,
+        threads: usize,
+    ) -> Result<BatchStats, RollError> {
+        let (_, expression) = expression_parse(text).unwrap();
+        roll_batch(
+            &expression,
+            iterations,
+            seed,
+            threads,
+            &FunctionRegistry::new(),
+            &EvalLimits::default(),
+        )
+    }
+
+    #[test]
+    fn batches_are_reproducible_on_any_number_of_threads() {
+        let stats = batch("4d6 k3", 50_000, 7, 1).unwrap();
+        assert_eq!(stats.iterations, 50_000);
+        assert_eq!(stats.min(), Some(3.into()));
+        assert_eq!(stats.max(), Some(18.into()));
+        assert!((stats.mean().unwrap() - 12.24).abs() < 0.1);
+
+        for threads in [2, 3, 8, 0] {
+            assert_eq!(batch("4d6 k3", 50_000, 7, threads), Ok(stats.clone()));
+        }
+        assert_ne!(batch("4d6 k3", 50_000, 8, 4), Ok(stats));
+    }
+
+    #[test]
+    fn batch_statistics_are_merged() {
+        let stats = batch("1d1 + 1d1", 10_000, 0, 4).unwrap();
+        assert_eq!(stats.totals.get(&2.into()), Some(&10_000));
+        assert_eq!(stats.std_dev(), Some(0.0));
+        assert_eq!(stats.at_least(3.into()), Some(0.0));
+
+        assert_eq!(batch("1d6", 0, 0, 4), Ok(BatchStats::default()));
+        assert_eq!(
+            batch("1d6 / 0", 10, 0, 4),
+            Err(RollError::Invalid("Division by zero"))
+        );
+    }
+
+    /// The exact chance of every total, found by going through every way the
+    /// dice can land and totalling the dice `total` picks out of the sorted roll
+    fn exact<F: Fn(&[i64]) -> i64>(
+        number_of_dice: u32,
+        faces: i64,
+        total: F,
+    ) -> BTreeMap<Rational, f64> {
+        let outcomes = faces.pow(number_of_dice);
+        let mut chances = BTreeMap::new();
+        for outcome in 0..outcomes {
+            let mut dice: Vec<i64> = (0..number_of_dice)
+                .map(|i| outcome / faces.pow(i) % faces + 1)
+                .collect();
+            dice.sort_unstable();
+            *chances.entry(total(&dice).into()).or_insert(0.0) += 1.0 / outcomes as f64;
+        }
+        chances
+    }
+
+    /// Pearson's chi-square statistic of the rolled totals against the exact
+    /// chances, along with its degrees of freedom
+    fn chi_square(stats: &BatchStats, chances: &BTreeMap<Rational, f64>) -> (f64, usize) {
+        assert!(stats.totals.keys().all(|total| chances.contains_key(total)));
+        let statistic = chances
+            .iter()
+            .map(|(total, chance)| {
+                let expected = chance * stats.iterations as f64;
+                let observed = stats.totals.get(total).copied().unwrap_or(0) as f64;
+                (observed - expected).powi(2) / expected
+            })
+            .sum();
+        (statistic, chances.len() - 1)
+    }
+
+    #[test]
+    fn exact_distributions_agree_with_monte_carlo() {
+        let sum = |dice: &[i64]| dice.iter().sum();
+        let cases: Vec<(&str, BTreeMap<Rational, f64>)> = vec![
+            ("3d6", exact(3, 6, sum)),
+            ("4d6 k3", exact(4, 6, |dice| sum(&dice[1..]))),
+            ("2d20 l1", exact(2, 20, |dice| dice[0])),
+            (
+                "5d4 r2 max3",
+                exact(5, 4, |dice| dice[2..].iter().map(|die| die.min(&3)).sum()),
+            ),
+        ];
+        for (text, chances) in cases {
+            let stats = batch(text, 100_000, 11, 0).unwrap();
+            let (statistic, freedom) = chi_square(&stats, &chances);
+            // A little past the 0.1% critical value for these degrees of freedom
+            let critical = freedom as f64 + 5.0 * (2.0 * freedom as f64).sqrt();
+            assert!(
+                statistic < critical,
+                "{} has a chi-square of {} with {} degrees of freedom",
+                text,
+                statistic,
+                freedom
+            );
+        }
+    }
+}
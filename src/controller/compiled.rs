@@ -0,0 +1,288 @@
+use super::{check_output_size, parse_expression, Evaluation, RollError, RollResult};
+use crate::calculation::die_source::DieSource;
+use crate::calculation::limits::EvalLimits;
+use crate::calculation::math_ops::scalar_calculate;
+use crate::calculation::{FunctionRegistry, RollScalar, TwoScalarCalculation};
+use crate::parsing::{Contest, Expression, Operation};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// An expression that has been parsed, checked and simplified once, so it can
+/// be rolled many times without any more work on the text. It's displayed in
+/// the canonical form of the expression as it was written.
+///
+/// ```
+/// use diceroll::controller::{CompiledRoll, RollResult};
+///
+/// let roll: CompiledRoll = "1D1+2*3".parse().unwrap();
+/// let mut rng = rand::thread_rng();
+/// for _ in 0..1000 {
+///     assert_eq!(roll.roll(&mut rng), Ok(RollResult::Total(7.into())));
+/// }
+/// assert_eq!(roll.to_string(), "1d1 + 2 * 3");
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct CompiledRoll {
+    text: String,
+    program: Program,
+    limits: EvalLimits,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+enum Program {
+    Expression(Expression),
+    Contest(Contest),
+}
+
+impl CompiledRoll {
+    /// Compile the text with the built-in functions and the default limits
+    pub fn new(text: &str) -> Result<CompiledRoll, RollError> {
+        CompiledRoll::compile(text, builtin_functions(), &EvalLimits::default())
+    }
+
+    /// Compile the text, checking that every function it calls is in `functions`.
+    /// Every roll of the compiled expression is held to `limits`.
+    pub fn compile(
+        text: &str,
+        functions: &FunctionRegistry,
+        limits: &EvalLimits,
+    ) -> Result<CompiledRoll, RollError> {
+        let contest_parse =
+            crate::parsing::contest::limited_contest_parse(limits.max_expression_depth);
+        // A contest has to take up the whole text, and so does an expression
+        let (text, program) = match contest_parse(text) {
+            Ok((rest, contest)) if rest.trim().is_empty() => (
+                contest.to_string(),
+                Program::Contest(Contest {
+                    attacker: optimize(contest.attacker, functions)?,
+                    defender: optimize(contest.defender, functions)?,
+                    tie_break: contest.tie_break,
+                }),
+            ),
+            _ => {
+                let expression = parse_expression(text, limits)?;
+                (
+                    expression.to_string(),
+                    Program::Expression(optimize(expression, functions)?),
+                )
+            }
+        };
+        Ok(CompiledRoll {
+            text,
+            program,
+            limits: *limits,
+        })
+    }
+
+    /// Roll the expression, calling the built-in functions
+    pub fn roll<R: DieSource>(&self, rng: &mut R) -> Result<RollResult, RollError> {
+        self.roll_with(rng, builtin_functions())
+    }
+
+    /// Roll the expression, calling the given functions by name
+    pub fn roll_with<R: DieSource>(
+        &self,
+        rng: &mut R,
+        functions: &FunctionRegistry,
+    ) -> Result<RollResult, RollError> {
+        let mut evaluation = Evaluation::new(rng, functions, self.limits);
+        let result = match &self.program {
+            Program::Contest(contest) => RollResult::Contest(evaluation.contest(contest)?),
+            Program::Expression(Expression::Roll(roll)) => {
+                evaluation.roll_with_result_mode(roll)?
+            }
+            Program::Expression(expression) => match evaluation.evaluate(expression)? {
+                RollScalar::Number(total) => RollResult::Total(total),
+                RollScalar::Roll(dice) => RollResult::Dice(dice.unwrap_or_default()),
+                RollScalar::Pool(pool) => {
+                    check_output_size(pool.len() as usize, &self.limits)?;
+                    RollResult::Dice(pool.to_vec())
+                }
+                RollScalar::Bool(b) => RollResult::Boolean(b),
+            },
+        };
+        match &result {
+            RollResult::Dice(dice) => check_output_size(dice.len(), &self.limits)?,
+            RollResult::Sets(sets) => check_output_size(sets.len(), &self.limits)?,
+            _ => (),
+        }
+        Ok(result)
+    }
+}
+
+impl FromStr for CompiledRoll {
+    type Err = RollError;
+
+    fn from_str(text: &str) -> Result<CompiledRoll, RollError> {
+        CompiledRoll::new(text)
+    }
+}
+
+impl fmt::Display for CompiledRoll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+fn builtin_functions() -> &'static FunctionRegistry {
+    static FUNCTIONS: OnceLock<FunctionRegistry> = OnceLock::new();
+    FUNCTIONS.get_or_init(FunctionRegistry::new)
+}
+
+/// Check the function names and work out the parts of the expression that
+/// don't depend on any dice. Anything that fails to calculate is left for the
+/// roll to report, since it might never be reached.
+fn optimize(expression: Expression, functions: &FunctionRegistry) -> Result<Expression, RollError> {
+    Ok(match expression {
+        Expression::Operation(first, operation, second) => {
+            let first = optimize(*first, functions)?;
+            let second = optimize(*second, functions)?;
+            match (&first, &second) {
+                (Expression::Number(a), Expression::Number(b)) => constant(*a, operation, *b)
+                    .map_or_else(
+                        || Expression::Operation(Box::new(first), operation, Box::new(second)),
+                        Expression::Number,
+                    ),
+                _ => Expression::Operation(Box::new(first), operation, Box::new(second)),
+            }
+        }
+        Expression::Negate(expression) => match optimize(*expression, functions)? {
+            Expression::Number(number) if number.checked_neg().is_some() => {
+                Expression::Number(-number)
+            }
+            expression => Expression::Negate(Box::new(expression)),
+        },
+        Expression::Not(expression) => Expression::Not(Box::new(optimize(*expression, functions)?)),
+        Expression::Function(name, arguments) => {
+            if !functions.contains(&name) {
+                return Err("Unknown function".into());
+            }
+            Expression::Function(
+                name.to_lowercase(),
+                arguments
+                    .into_iter()
+                    .map(|argument| optimize(argument, functions))
+                    .collect::<Result<Vec<Expression>, RollError>>()?,
+            )
+        }
+        Expression::Conditional(condition, first, second) => {
+            let condition = optimize(*condition, functions)?;
+            let first = optimize(*first, functions)?;
+            let second = optimize(*second, functions)?;
+            match condition {
+                Expression::Number(number) if number != 0 => first,
+                Expression::Number(_) => second,
+                condition => {
+                    Expression::Conditional(Box::new(condition), Box::new(first), Box::new(second))
+                }
+            }
+        }
+        expression => expression,
+    })
+}
+
+/// The arithmetic on two numbers, when it comes to an integer
+fn constant(first: i64, operation: Operation, second: i64) -> Option<i64> {
+    if !matches!(
+        operation,
+        Operation::Add
+            | Operation::Sub
+            | Operation::Mul
+            | Operation::Div
+            | Operation::ExactDiv
+            | Operation::Mod
+    ) {
+        return None;
+    }
+    let result = scalar_calculate(&TwoScalarCalculation {
+        operation: super::calculation_operation(operation),
+        first: RollScalar::Number(first.into()),
+        second: RollScalar::Number(second.into()),
+    })
+    .ok()?;
+    match result {
+        RollScalar::Number(number) if number.is_integer() => Some(number.numerator()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(text: &str) -> Expression {
+        match CompiledRoll::new(text).unwrap().program {
+            Program::Expression(expression) => expression,
+            Program::Contest(_) => panic!("Not an expression"),
+        }
+    }
+
+    #[test]
+    fn constant_parts_are_worked_out_once() {
+        assert_eq!(compiled("2 * 3 + 4"), Expression::Number(10));
+        assert_eq!(compiled("-(7 / 2)"), Expression::Number(-3));
+        assert_eq!(compiled("if 1 - 1 then 1d4 else 5"), Expression::Number(5));
+        // Fractions and failures are left to the roll
+        assert!(matches!(compiled("7 // 2"), Expression::Operation(..)));
+        assert!(matches!(compiled("1 / 0"), Expression::Operation(..)));
+        assert!(matches!(
+            compiled("1d6 + 2 * 3"),
+            Expression::Operation(_, Operation::Add, _)
+        ));
+    }
+
+    #[test]
+    fn functions_are_checked_when_compiling() {
+        assert_eq!(
+            CompiledRoll::new("MAX(1d1, 2)").map(|roll| roll.roll(&mut rand::thread_rng())),
+            Ok(Ok(RollResult::Total(2.into())))
+        );
+        assert_eq!(
+            "nope(1d6)".parse::<CompiledRoll>(),
+            Err(RollError::Invalid("Unknown function"))
+        );
+
+        let mut functions = FunctionRegistry::empty();
+        functions.register("nope", |_| Ok(RollScalar::Bool(true)));
+        let roll = CompiledRoll::compile("nope(1d6)", &functions, &EvalLimits::default()).unwrap();
+        assert_eq!(
+            roll.roll_with(&mut rand::thread_rng(), &functions),
+            Ok(RollResult::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn compiled_rolls_show_their_canonical_form() {
+        let roll: CompiledRoll = " 3d1K2 ".parse().unwrap();
+        assert_eq!(roll.to_string(), "3d1 k2");
+        assert_eq!(
+            roll.roll(&mut rand::thread_rng()),
+            Ok(RollResult::Dice(vec![1, 1]))
+        );
+        assert_eq!(
+            "1d1 vs 1d1 ties defender"
+                .parse::<CompiledRoll>()
+                .map(|roll| roll.to_string()),
+            Ok("1d1 vs 1d1".to_string())
+        );
+    }
+
+    #[test]
+    fn text_left_over_is_invalid() {
+        for text in [
+            "1d1 + 5 foo",
+            "1d1 )+ 5",
+            "1d1 vs 1d1 foo",
+            "1d1 vs 1d1 ties nobody",
+            "2g1y",
+        ] {
+            assert_eq!(
+                text.parse::<CompiledRoll>(),
+                Err(RollError::Invalid("Invalid dice roll format")),
+                "{}",
+                text
+            );
+        }
+    }
+}
@@ -1,14 +1,403 @@
 extern crate rand;
 
-use crate::calculation::RollRequest;
-use crate::parsing::{ComplexDiceRoll, DiceRoll, RollModType};
+pub mod batch;
+pub mod compiled;
+pub mod verifiable;
 
-pub fn parse_and_roll_dice(text: &str) -> Result<Vec<u64>, &str> {
+pub use compiled::CompiledRoll;
+
+use crate::calculation::comparison::{self, resolve_contest, ContestResult};
+use crate::calculation::die_source::{Borrowed, DieSource};
+use crate::calculation::limits::{EvalLimits, LimitExceeded};
+use crate::calculation::math_ops::{
+    roll_scalar_to_bool, roll_scalar_to_number, scalar_calculate, scalar_negate,
+};
+use crate::calculation::symbol_dice::{self, SymbolDie, SymbolRollResult};
+use crate::calculation::{
+    self, DiceSet, FunctionRegistry, Rational, RollRequest, RollScalar, TwoScalarCalculation,
+    WildDieRoll,
+};
+use crate::parsing::{
+    ComplexDiceRoll, Contest, DiceRoll, Expression, Operation, RollModType, SymbolDiceRoll,
+    SymbolDieType, TieBreak,
+};
+use std::fmt;
+
+/// The result of a roll, shaped by the result mode modifiers in the expression
+#[derive(PartialEq, Debug, Clone)]
+pub enum RollResult {
+    Dice(Vec<i64>),
+    Sets(Vec<DiceSet>),
+    Wild(WildDieRoll),
+    Total(Rational),
+    Boolean(bool),
+    Contest(ContestResult),
+}
+
+/// Why parsing or rolling failed
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RollError {
+    /// The text isn't a valid roll, or its value can't be calculated
+    Invalid(&'static str),
+    /// The roll went over one of its `EvalLimits`
+    Limit(LimitExceeded),
+}
+
+impl RollError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            RollError::Invalid(message) => message,
+            RollError::Limit(exceeded) => exceeded.message(),
+        }
+    }
+}
+
+impl From<&'static str> for RollError {
+    fn from(message: &'static str) -> RollError {
+        RollError::Invalid(message)
+    }
+}
+
+impl From<LimitExceeded> for RollError {
+    fn from(exceeded: LimitExceeded) -> RollError {
+        RollError::Limit(exceeded)
+    }
+}
+
+impl fmt::Display for RollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RollError::Invalid(message) => write!(f, "{}", message),
+            RollError::Limit(exceeded) => write!(f, "{}", exceeded),
+        }
+    }
+}
+
+pub fn parse_and_roll_dice(text: &str) -> Result<Vec<i64>, &str> {
     let dice_roll_parse = parse_dice(text)?;
-    let roll_request = roll_complex_dice(dice_roll_parse);
-    match roll_request {
+    let limits = EvalLimits::default();
+    let functions = FunctionRegistry::empty();
+    let mut rng = rand::thread_rng();
+    let mut evaluation = Evaluation::new(&mut rng, &functions, limits);
+    let (roll_request, _) = evaluation.roll(&dice_roll_parse).map_err(|e| e.message())?;
+    check_output_size(roll_request.dice_count() as usize, &limits).map_err(|e| e.message())?;
+    roll_request.as_vec().ok_or("No dice left to roll!")
+}
+
+/// Parse and roll the dice like `parse_and_roll_dice`, returning the dice
+/// grouped into sets when the expression contains the `sets` modifier, or the
+/// trait roll when it contains a wild die. Expressions that combine dice rolls
+/// with arithmetic return their total or, for comparisons, the dice that passed
+/// or a boolean. Contests such as `1d20+7 vs 1d20+4` return both totals and the
+/// winner.
+pub fn parse_and_roll(text: &str) -> Result<RollResult, &str> {
+    parse_and_roll_with_functions(text, &FunctionRegistry::new())
+}
+
+/// Parse and roll like `parse_and_roll`, calling the given functions by name
+pub fn parse_and_roll_with_functions<'a>(
+    text: &'a str,
+    functions: &FunctionRegistry,
+) -> Result<RollResult, &'a str> {
+    parse_and_roll_with(text, functions, &EvalLimits::default()).map_err(|e| e.message())
+}
+
+/// Parse and roll like `parse_and_roll`, calling the given functions by name and
+/// failing when the roll goes over `limits`
+///
+/// ```
+/// use diceroll::calculation::limits::{EvalLimits, LimitExceeded};
+/// use diceroll::calculation::FunctionRegistry;
+/// use diceroll::controller::{parse_and_roll_with, RollError};
+///
+/// let limits = EvalLimits {
+///     max_total_dice: 20,
+///     ..EvalLimits::default()
+/// };
+/// assert_eq!(
+///     parse_and_roll_with("10d6 + 11d6", &FunctionRegistry::new(), &limits),
+///     Err(RollError::Limit(LimitExceeded::TotalDice { limit: 20 }))
+/// );
+/// ```
+pub fn parse_and_roll_with(
+    text: &str,
+    functions: &FunctionRegistry,
+    limits: &EvalLimits,
+) -> Result<RollResult, RollError> {
+    CompiledRoll::compile(text, functions, limits)?.roll_with(&mut rand::thread_rng(), functions)
+}
+
+/// Roll every dice roll in the expression and calculate the total, counting
+/// each roll as the sum of its values
+pub fn calculate_expression<R: DieSource>(
+    expression: &Expression,
+    rng: &mut R,
+) -> Result<Rational, &'static str> {
+    roll_scalar_to_number(&evaluate_expression(expression, rng)?)
+}
+
+/// Roll every dice roll in the expression and calculate its value. Only the
+/// chosen branch of a conditional is rolled.
+pub fn evaluate_expression<R: DieSource>(
+    expression: &Expression,
+    rng: &mut R,
+) -> Result<RollScalar, &'static str> {
+    evaluate_with_functions(expression, rng, &FunctionRegistry::new())
+}
+
+/// Evaluate the expression like `evaluate_expression`, calling the given
+/// functions by name
+pub fn evaluate_with_functions<R: DieSource>(
+    expression: &Expression,
+    rng: &mut R,
+    functions: &FunctionRegistry,
+) -> Result<RollScalar, &'static str> {
+    evaluate_with(expression, rng, functions, &EvalLimits::default()).map_err(|e| e.message())
+}
+
+/// Evaluate the expression like `evaluate_expression`, calling the given
+/// functions by name and failing when it goes over `limits`
+pub fn evaluate_with<R: DieSource>(
+    expression: &Expression,
+    rng: &mut R,
+    functions: &FunctionRegistry,
+    limits: &EvalLimits,
+) -> Result<RollScalar, RollError> {
+    Evaluation::new(rng, functions, *limits).evaluate(expression)
+}
+
+/// Roll both sides of the contest and pick a winner
+pub fn roll_contest<R: DieSource>(
+    contest: &Contest,
+    rng: &mut R,
+) -> Result<ContestResult, &'static str> {
+    let functions = FunctionRegistry::new();
+    Evaluation::new(rng, &functions, EvalLimits::default())
+        .contest(contest)
+        .map_err(|e| e.message())
+}
+
+/// The state of a roll while its expression is evaluated, keeping count of the
+/// dice rolled so far
+struct Evaluation<'a, R: DieSource> {
+    rng: &'a mut R,
+    functions: &'a FunctionRegistry,
+    limits: EvalLimits,
+    dice_rolled: u64,
+    dice_pooled: u64,
+}
+
+impl<'a, R: DieSource> Evaluation<'a, R> {
+    fn new(rng: &'a mut R, functions: &'a FunctionRegistry, limits: EvalLimits) -> Self {
+        Evaluation {
+            rng,
+            functions,
+            limits,
+            dice_rolled: 0,
+            dice_pooled: 0,
+        }
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> Result<RollScalar, RollError> {
+        self.scalar(expression, self.limits.max_expression_depth)
+    }
+
+    fn contest(&mut self, contest: &Contest) -> Result<ContestResult, RollError> {
+        let tie_break = match contest.tie_break {
+            TieBreak::Attacker => comparison::TieBreak::Attacker,
+            TieBreak::Defender => comparison::TieBreak::Defender,
+            TieBreak::Draw => comparison::TieBreak::Draw,
+        };
+        let attacker = self.evaluate(&contest.attacker)?;
+        let defender = self.evaluate(&contest.defender)?;
+        Ok(resolve_contest(&attacker, &defender, tie_break)?)
+    }
+
+    /// Evaluate the expression, which can nest `depth` more levels
+    fn scalar(&mut self, expression: &Expression, depth: usize) -> Result<RollScalar, RollError> {
+        let depth = depth.checked_sub(1).ok_or(LimitExceeded::ExpressionDepth {
+            limit: self.limits.max_expression_depth,
+        })?;
+        Ok(match expression {
+            Expression::Roll(roll) => {
+                let (roll_request, _) = self.roll(roll)?;
+                match roll_request.as_pool() {
+                    Some(pool) => RollScalar::Pool(pool.clone()),
+                    None => RollScalar::Roll(roll_request.as_vec()),
+                }
+            }
+            Expression::Number(number) => RollScalar::Number(Rational::from_integer(*number)),
+            Expression::Operation(first, operation, second) => {
+                scalar_calculate(&TwoScalarCalculation {
+                    operation: calculation_operation(*operation),
+                    first: self.scalar(first, depth)?,
+                    second: self.scalar(second, depth)?,
+                })?
+            }
+            Expression::Negate(expression) => scalar_negate(&self.scalar(expression, depth)?)?,
+            Expression::Not(expression) => {
+                RollScalar::Bool(!roll_scalar_to_bool(&self.scalar(expression, depth)?))
+            }
+            Expression::Function(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.scalar(argument, depth))
+                    .collect::<Result<Vec<RollScalar>, RollError>>()?;
+                self.functions.call(name, &arguments)?
+            }
+            Expression::Conditional(condition, first, second) => {
+                if roll_scalar_to_bool(&self.scalar(condition, depth)?) {
+                    self.scalar(first, depth)?
+                } else {
+                    self.scalar(second, depth)?
+                }
+            }
+        })
+    }
+
+    fn roll_with_result_mode(
+        &mut self,
+        dice_roll_parse: &ComplexDiceRoll,
+    ) -> Result<RollResult, RollError> {
+        let sets_mode = dice_roll_parse
+            .roll_mods
+            .iter()
+            .any(|m| m.roll_mod_type == RollModType::Sets);
+        let limits = self.limits;
+        let (roll_request, wild_die_roll) = self.roll(dice_roll_parse)?;
+        let result = if sets_mode {
+            roll_request.sets().map(RollResult::Sets)
+        } else if let Some(wild_die_roll) = wild_die_roll {
+            Some(RollResult::Wild(wild_die_roll))
+        } else {
+            check_output_size(roll_request.dice_count() as usize, &limits)?;
+            roll_request.as_vec().map(RollResult::Dice)
+        };
+        Ok(result.ok_or("No dice left to roll!")?)
+    }
+
+    /// Roll the dice, sharing the limit on the total number of dice with every
+    /// other roll in the expression
+    fn roll(
+        &mut self,
+        roll: &ComplexDiceRoll,
+    ) -> Result<(RollRequest<Borrowed<'_, R>>, Option<WildDieRoll>), RollError> {
+        let limits = EvalLimits {
+            max_total_dice: self.limits.max_total_dice.saturating_sub(self.dice_rolled),
+            max_total_pooled_dice: self
+                .limits
+                .max_total_pooled_dice
+                .saturating_sub(self.dice_pooled),
+            ..self.limits
+        };
+        let (roll_request, wild_die_roll) = roll_complex_dice(roll, Borrowed(self.rng), limits);
+        self.dice_rolled += roll_request.dice_rolled();
+        self.dice_pooled += roll_request.dice_pooled();
+        if let Some(message) = roll_request.failure() {
+            return Err(message.into());
+        }
+        match roll_request.exceeded() {
+            Some(LimitExceeded::TotalDice { .. }) => Err(LimitExceeded::TotalDice {
+                limit: self.limits.max_total_dice,
+            }
+            .into()),
+            Some(LimitExceeded::TotalPooledDice { .. }) => Err(LimitExceeded::TotalPooledDice {
+                limit: self.limits.max_total_pooled_dice,
+            }
+            .into()),
+            Some(exceeded) => Err(exceeded.into()),
+            None => Ok((roll_request, wild_die_roll)),
+        }
+    }
+}
+
+fn check_output_size(size: usize, limits: &EvalLimits) -> Result<(), RollError> {
+    if size > limits.max_output_size {
+        Err(LimitExceeded::OutputSize {
+            limit: limits.max_output_size,
+            size,
+        }
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+fn calculation_operation(operation: Operation) -> calculation::Operation {
+    match operation {
+        Operation::Add => calculation::Operation::Add,
+        Operation::Sub => calculation::Operation::Sub,
+        Operation::Mul => calculation::Operation::Mul,
+        Operation::Div => calculation::Operation::Div,
+        Operation::ExactDiv => calculation::Operation::ExactDiv,
+        Operation::Mod => calculation::Operation::Mod,
+        Operation::Eq => calculation::Operation::Eq,
+        Operation::Ne => calculation::Operation::Ne,
+        Operation::Lt => calculation::Operation::Lt,
+        Operation::Le => calculation::Operation::Le,
+        Operation::Gt => calculation::Operation::Gt,
+        Operation::Ge => calculation::Operation::Ge,
+        Operation::And => calculation::Operation::And,
+        Operation::Or => calculation::Operation::Or,
+    }
+}
+
+/// Parse and roll a pool of symbol dice such as `2g1y2p`
+pub fn parse_and_roll_symbols(text: &str) -> Result<SymbolRollResult, &str> {
+    parse_and_roll_symbols_with(text, rand::thread_rng())
+}
+
+/// Parse and roll a pool of symbol dice like `parse_and_roll_symbols`, with the
+/// given random number generator
+pub fn parse_and_roll_symbols_with<R: DieSource>(
+    text: &str,
+    rng: R,
+) -> Result<SymbolRollResult, &str> {
+    let pool = match crate::parsing::symbol_dice::symbol_dice_pool_parse(text) {
+        Ok((rest, successful_parsed_pool)) if rest.trim().is_empty() => successful_parsed_pool,
+        _ => return Err("Invalid symbol dice format"),
+    };
+    let mut roll_request = RollRequest::new(rng);
+    match roll_request.roll_symbol_dice(&symbol_pool(&pool)) {
         Some(result) => Ok(result),
-        None => Err("No dice left to roll!"),
+        None => Err(roll_request
+            .failure()
+            .or_else(|| roll_request.exceeded().map(|exceeded| exceeded.message()))
+            .unwrap_or("No dice left to roll!")),
+    }
+}
+
+fn symbol_pool(pool: &[SymbolDiceRoll]) -> Vec<(u64, SymbolDie)> {
+    pool.iter()
+        .map(|roll| {
+            let die = match roll.die_type {
+                SymbolDieType::Boost => symbol_dice::BOOST,
+                SymbolDieType::Setback => symbol_dice::SETBACK,
+                SymbolDieType::Ability => symbol_dice::ABILITY,
+                SymbolDieType::Difficulty => symbol_dice::DIFFICULTY,
+                SymbolDieType::Proficiency => symbol_dice::PROFICIENCY,
+                SymbolDieType::Challenge => symbol_dice::CHALLENGE,
+                SymbolDieType::Force => symbol_dice::FORCE,
+            };
+            (roll.number_of_dice, die)
+        })
+        .collect()
+}
+
+fn parse_expression(text: &str, limits: &EvalLimits) -> Result<Expression, RollError> {
+    let expression_parse =
+        crate::parsing::math_ops::limited_expression_parse(limits.max_expression_depth);
+    match expression_parse(text) {
+        Ok((rest, successful_parsed_expression)) if rest.trim().is_empty() => {
+            Ok(successful_parsed_expression)
+        }
+        Err(nom::Err::Failure((_, nom::error::ErrorKind::TooLarge))) => {
+            Err(LimitExceeded::ExpressionDepth {
+                limit: limits.max_expression_depth,
+            }
+            .into())
+        }
+        _ => Err("Invalid dice roll format".into()),
     }
 }
 
@@ -19,22 +408,43 @@ fn parse_dice(text: &str) -> Result<ComplexDiceRoll, &str> {
     }
 }
 
-fn roll_complex_dice(roll: ComplexDiceRoll) -> Option<Vec<u64>> {
-    let mut roll_request = roll_simple_dice(roll.dice_roll.clone());
-    for m in roll.roll_mods {
+fn roll_complex_dice<R: DieSource>(
+    roll: &ComplexDiceRoll,
+    rng: R,
+    limits: EvalLimits,
+) -> (RollRequest<R>, Option<WildDieRoll>) {
+    let mut roll_request = roll_simple_dice(roll.dice_roll, rng, limits);
+    let mut wild_die_roll = None;
+    for m in &roll.roll_mods {
         match m.roll_mod_type {
             RollModType::E => roll_request.explode(m.value, roll.dice_roll.dice_range),
-            RollModType::R => roll_request.remove(m.value),
-            RollModType::K => roll_request.keep(m.value),
-            RollModType::L => roll_request.keep_lower(m.value),
+            RollModType::R => roll_request.remove(mod_count(m.value)),
+            RollModType::K => roll_request.keep(mod_count(m.value)),
+            RollModType::L => roll_request.keep_lower(mod_count(m.value)),
+            RollModType::Min => roll_request.clamp_min(m.value),
+            RollModType::Max => roll_request.clamp_max(m.value),
+            RollModType::Sa => roll_request.sort_ascending(),
+            RollModType::Sd => roll_request.sort_descending(),
+            RollModType::Sets => &mut roll_request,
+            RollModType::W => {
+                wild_die_roll =
+                    Some(roll_request.wild_die(roll.dice_roll.dice_range, mod_count(m.value)));
+                &mut roll_request
+            }
         };
     }
-    roll_request.as_vec()
+    (roll_request, wild_die_roll)
 }
 
-fn roll_simple_dice(parser: DiceRoll) -> RollRequest<rand::prelude::ThreadRng> {
-    let rng = rand::thread_rng();
+/// Keep, remove and wild die values are counts, which the parser never gives
+/// as negative. A negative count in an expression built by hand counts as 0.
+fn mod_count(value: i64) -> u64 {
+    value.max(0) as u64
+}
+
+fn roll_simple_dice<R: DieSource>(parser: DiceRoll, rng: R, limits: EvalLimits) -> RollRequest<R> {
     let mut roll_request = RollRequest::new(rng);
+    roll_request.with_limits(limits);
 
     roll_request.roll_dice(parser.number_of_dice, parser.dice_range);
     roll_request
@@ -43,9 +453,520 @@ fn roll_simple_dice(parser: DiceRoll) -> RollRequest<rand::prelude::ThreadRng> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calculation::die_source::ManualDice;
+    use crate::parsing::strategies::{small_expression, small_roll, token_soup};
+    use proptest::prelude::*;
+    use rand::SeedableRng;
 
     #[test]
     fn dice_roll_calculates_as_expected() {
         assert_eq!(parse_and_roll_dice("17d1 k11 r6"), Ok(vec![1, 1, 1, 1, 1]));
     }
+
+    #[test]
+    fn clamp_modifiers_change_values_before_keep() {
+        assert_eq!(parse_and_roll_dice("5d1 min3 k2"), Ok(vec![3, 3]));
+        assert_eq!(
+            parse_and_roll_dice("5d1 min4 max2"),
+            Ok(vec![2, 2, 2, 2, 2])
+        );
+    }
+
+    #[test]
+    fn sets_modifier_groups_the_result() {
+        assert_eq!(
+            parse_and_roll("7d1 sets"),
+            Ok(RollResult::Sets(vec![DiceSet { face: 1, count: 7 }]))
+        );
+        assert_eq!(parse_and_roll("3d1 k2"), Ok(RollResult::Dice(vec![1, 1])));
+        assert_eq!(parse_and_roll("3d1 r3 sets"), Err("No dice left to roll!"));
+    }
+
+    #[test]
+    fn wild_die_reports_the_trait_roll() {
+        assert_eq!(
+            parse_and_roll("1d1 w1"),
+            Ok(RollResult::Wild(WildDieRoll {
+                trait_roll: 1,
+                wild_roll: 1,
+                total: 1,
+                used_wild: false,
+                critical_failure: true,
+            }))
+        );
+        assert_eq!(parse_and_roll_dice("3d1 wild d1"), Ok(vec![1]));
+    }
+
+    #[test]
+    fn expressions_with_arithmetic_return_the_total() {
+        assert_eq!(parse_and_roll("2d1 + 3"), Ok(RollResult::Total(5.into())));
+        assert_eq!(
+            parse_and_roll("(3d1 k2 + 4) * 2"),
+            Ok(RollResult::Total(12.into()))
+        );
+        assert_eq!(
+            parse_and_roll("3d1 r3 - 1"),
+            Ok(RollResult::Total((-1).into()))
+        );
+        assert_eq!(parse_and_roll("(2d1)"), Ok(RollResult::Dice(vec![1, 1])));
+    }
+
+    #[test]
+    fn text_left_over_after_the_expression_is_invalid() {
+        for text in ["1d1 + 5 foo", "1d1 )+ 5", "1d0", "2g1y"] {
+            assert_eq!(
+                parse_and_roll(text),
+                Err("Invalid dice roll format"),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(parse_and_roll(" 1d1 + 5 "), Ok(RollResult::Total(6.into())));
+
+        // The command line rolls symbol dice when the text isn't an expression
+        assert_eq!(
+            CompiledRoll::new("2g1y").err(),
+            Some(RollError::Invalid("Invalid dice roll format"))
+        );
+        assert_eq!(parse_and_roll_symbols("2g1y").unwrap().faces.len(), 3);
+    }
+
+    #[test]
+    fn calculate_expression_uses_the_given_rng() {
+        let mut rng = rand::rngs::mock::StepRng::new(2, 1);
+        let expression = parse_expression("4d20 k3 + 2", &EvalLimits::default()).unwrap();
+        assert_eq!(calculate_expression(&expression, &mut rng), Ok(5.into()));
+    }
+
+    #[test]
+    fn dice_without_faces_are_invalid() {
+        let expression = Expression::Roll(ComplexDiceRoll {
+            dice_roll: DiceRoll {
+                number_of_dice: 2,
+                dice_range: 0,
+            },
+            roll_mods: vec![],
+        });
+        assert_eq!(
+            calculate_expression(&expression, &mut rand::thread_rng()),
+            Err("A die needs at least one face")
+        );
+    }
+
+    #[test]
+    fn division_rounds_down_unless_exact() {
+        assert_eq!(parse_and_roll("3d1 / 2"), Ok(RollResult::Total(1.into())));
+        assert_eq!(
+            parse_and_roll("3d1 // 2"),
+            Ok(RollResult::Total(Rational::new(3, 2).unwrap()))
+        );
+        assert_eq!(
+            parse_and_roll("ceil(3d1 // 2) + 7 % 4"),
+            Ok(RollResult::Total(5.into()))
+        );
+        assert_eq!(parse_and_roll("1d1 / 0"), Err("Division by zero"));
+        assert_eq!(parse_and_roll("1d1 % (2 - 2)"), Err("Division by zero"));
+    }
+
+    #[test]
+    fn functions_are_called_by_name() {
+        assert_eq!(
+            parse_and_roll("max(1d1, 10)"),
+            Ok(RollResult::Total(10.into()))
+        );
+        assert_eq!(
+            parse_and_roll("min(4d1k3, 15) + abs(1d1 - 3d1)"),
+            Ok(RollResult::Total(5.into()))
+        );
+        assert_eq!(
+            parse_and_roll("count(8d1 >= 1) + avg(3d1)"),
+            Ok(RollResult::Total(9.into()))
+        );
+        assert_eq!(parse_and_roll("nope(1d6)"), Err("Unknown function"));
+    }
+
+    #[test]
+    fn registered_functions_can_be_rolled() {
+        let mut functions = FunctionRegistry::new();
+        functions.register("mod", |args| match args {
+            [score] => roll_scalar_to_number(score)?
+                .checked_sub(10.into())
+                .and_then(|difference| difference.checked_floor_div(2.into()))
+                .map(RollScalar::Number)
+                .ok_or("Integer overflow"),
+            _ => Err("Wrong number of arguments"),
+        });
+
+        assert_eq!(
+            parse_and_roll_with_functions("1d1 + mod(3d1)", &functions),
+            Ok(RollResult::Total((-3).into()))
+        );
+        assert_eq!(parse_and_roll("mod(3d6)"), Err("Unknown function"));
+    }
+
+    #[test]
+    fn functions_can_start_with_a_keyword() {
+        let mut functions = FunctionRegistry::new();
+        for name in ["nothing", "android", "orc", "iffy"] {
+            functions.register(name, |_| Ok(RollScalar::Number(7.into())));
+        }
+
+        assert_eq!(
+            parse_and_roll_with_functions("nothing(2)", &functions),
+            Ok(RollResult::Total(7.into()))
+        );
+        assert_eq!(
+            parse_and_roll_with_functions("if iffy(1) then android(1) + orc(1) else 0", &functions),
+            Ok(RollResult::Total(14.into()))
+        );
+        assert_eq!(
+            parse_and_roll_with_functions("not nothing(1) or_else", &functions),
+            Err("Invalid dice roll format")
+        );
+        assert_eq!(
+            parse_and_roll_with_functions("not(0) and 1d1", &functions),
+            Ok(RollResult::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn negative_values_roll_through() {
+        assert_eq!(
+            parse_and_roll("1d1 - 5"),
+            Ok(RollResult::Total((-4).into()))
+        );
+        assert_eq!(
+            parse_and_roll("2d1 + -3"),
+            Ok(RollResult::Total((-1).into()))
+        );
+        assert_eq!(parse_and_roll("-2d1"), Ok(RollResult::Dice(vec![-1, -1])));
+        assert_eq!(
+            parse_and_roll("-3d1 >= -1"),
+            Ok(RollResult::Dice(vec![-1, -1, -1]))
+        );
+        assert_eq!(
+            parse_and_roll("abs(-2d1) * -1"),
+            Ok(RollResult::Total((-2).into()))
+        );
+        assert_eq!(parse_and_roll_dice("3d1 min-2"), Ok(vec![1, 1, 1]));
+        assert_eq!(
+            parse_and_roll("9223372036854775807 + 1d1"),
+            Err("Integer overflow")
+        );
+    }
+
+    #[test]
+    fn rolls_over_the_default_limits_fail() {
+        assert_eq!(
+            parse_and_roll_dice("18446744073709551615d18446744073709551615"),
+            Err("Too many dice in one roll")
+        );
+        assert_eq!(
+            parse_and_roll("1d6 + 2d18446744073709551615"),
+            Err("Too many faces on a die")
+        );
+        assert_eq!(parse_and_roll("2d1e1"), Err("Too many explosions in a row"));
+        assert_eq!(
+            parse_and_roll(&format!("{}1{}", "(".repeat(500), ")".repeat(500))),
+            Err("Expression is nested too deeply")
+        );
+        assert_eq!(
+            parse_and_roll_symbols("99999999999g"),
+            Err("Too many dice in one roll")
+        );
+        assert_eq!(
+            parse_and_roll_symbols(&"10000g".repeat(11)),
+            Err("Too many dice rolled")
+        );
+    }
+
+    #[test]
+    fn large_rolls_are_totalled_without_listing_every_die() {
+        assert_eq!(
+            parse_and_roll("1000000d1 + 0"),
+            Ok(RollResult::Total(1_000_000.into()))
+        );
+        assert_eq!(
+            parse_and_roll("count(1000000d1 >= 1) - sum(2000000d1 k1000000)"),
+            Ok(RollResult::Total(0.into()))
+        );
+        assert_eq!(
+            parse_and_roll("1000000d1 sets"),
+            Ok(RollResult::Sets(vec![DiceSet {
+                face: 1,
+                count: 1_000_000
+            }]))
+        );
+        assert_eq!(
+            parse_and_roll("-(20000d1 k3)"),
+            Ok(RollResult::Dice(vec![-1, -1, -1]))
+        );
+        assert_eq!(parse_and_roll("1000000d6"), Err("Result is too large"));
+        assert_eq!(
+            parse_and_roll_dice("1000000d6 k5 min7"),
+            Ok(vec![7, 7, 7, 7, 7])
+        );
+    }
+
+    #[test]
+    fn limits_are_reported_as_structured_errors() {
+        let functions = FunctionRegistry::new();
+        let limits = EvalLimits {
+            max_dice_per_roll: 10,
+            max_total_dice: 15,
+            max_explosion_depth: 5,
+            max_expression_depth: 4,
+            max_output_size: 8,
+            ..EvalLimits::default()
+        };
+        let roll = |text| parse_and_roll_with(text, &functions, &limits);
+
+        assert_eq!(roll("10d1 k2"), Ok(RollResult::Dice(vec![1, 1])));
+        assert_eq!(
+            roll("11d1"),
+            Err(RollError::Limit(LimitExceeded::DicePerRoll {
+                limit: 10,
+                requested: 11
+            }))
+        );
+        assert_eq!(
+            roll("8d1 + 8d1"),
+            Err(RollError::Limit(LimitExceeded::TotalDice { limit: 15 }))
+        );
+
+        // Pooled dice have a total of their own
+        let pooled_limits = EvalLimits {
+            max_total_pooled_dice: 30_000,
+            ..limits
+        };
+        let roll_pooled = |text| parse_and_roll_with(text, &functions, &pooled_limits);
+        assert!(roll_pooled("sum(20000d6)").is_ok());
+        assert_eq!(
+            roll_pooled("sum(20000d6) + sum(20000d6)"),
+            Err(RollError::Limit(LimitExceeded::TotalPooledDice {
+                limit: 30_000
+            }))
+        );
+        assert_eq!(
+            roll("9d1"),
+            Err(RollError::Limit(LimitExceeded::OutputSize {
+                limit: 8,
+                size: 9
+            }))
+        );
+        assert_eq!(
+            roll("1d1 e1"),
+            Err(RollError::Limit(LimitExceeded::ExplosionDepth { limit: 5 }))
+        );
+        assert_eq!(
+            roll("-(-(-(1)))"),
+            Err(RollError::Limit(LimitExceeded::ExpressionDepth {
+                limit: 4
+            }))
+        );
+        assert_eq!(roll("1d1 / 0"), Err(RollError::Invalid("Division by zero")));
+    }
+
+    #[test]
+    fn evaluation_depth_is_limited_for_built_expressions() {
+        let mut expression = Expression::Number(1);
+        for _ in 0..10 {
+            expression = Expression::Negate(Box::new(expression));
+        }
+        let limits = EvalLimits {
+            max_expression_depth: 10,
+            ..EvalLimits::default()
+        };
+        let mut rng = rand::rngs::mock::StepRng::new(2, 1);
+
+        assert_eq!(
+            evaluate_with(&expression, &mut rng, &FunctionRegistry::new(), &limits),
+            Err(RollError::Limit(LimitExceeded::ExpressionDepth {
+                limit: 10
+            }))
+        );
+        assert_eq!(
+            evaluate_expression(&expression, &mut rng),
+            Ok(RollScalar::Number(1.into()))
+        );
+    }
+
+    #[test]
+    fn comparisons_give_pass_or_fail() {
+        assert_eq!(parse_and_roll("1d1+5 >= 6"), Ok(RollResult::Boolean(true)));
+        assert_eq!(parse_and_roll("1d1+5 > 6"), Ok(RollResult::Boolean(false)));
+        assert_eq!(
+            parse_and_roll("not 1d1 > 1 and 2 < 3"),
+            Ok(RollResult::Boolean(true))
+        );
+        assert_eq!(
+            parse_and_roll("3d1 >= 1"),
+            Ok(RollResult::Dice(vec![1, 1, 1]))
+        );
+        assert_eq!(parse_and_roll("3d1 > 1"), Ok(RollResult::Dice(vec![])));
+        assert_eq!(
+            parse_and_roll("1 <= 3d1"),
+            Ok(RollResult::Dice(vec![1, 1, 1]))
+        );
+        assert_eq!(parse_and_roll("1 < 3d1"), Ok(RollResult::Dice(vec![])));
+        assert_eq!(
+            parse_and_roll("sum(3d1) >= 3"),
+            Ok(RollResult::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn conditionals_only_roll_the_chosen_branch() {
+        assert_eq!(
+            parse_and_roll("if 1d1 >= 1 then 2d1 + 1 else 1d1"),
+            Ok(RollResult::Total(3.into()))
+        );
+        assert_eq!(
+            parse_and_roll("if 1d1 > 1 then 2d1 else 5"),
+            Ok(RollResult::Total(5.into()))
+        );
+
+        // StepRng rolls its next die from the chosen branch
+        let mut rng = rand::rngs::mock::StepRng::new(2, 1);
+        let expression =
+            parse_expression("if 0 then 1d6 else 1d6", &EvalLimits::default()).unwrap();
+        assert_eq!(
+            evaluate_expression(&expression, &mut rng),
+            Ok(RollScalar::Roll(Some(vec![1])))
+        );
+    }
+
+    #[test]
+    fn contests_report_the_margin_and_winner() {
+        assert_eq!(
+            parse_and_roll("1d1+7 vs 1d1+4"),
+            Ok(RollResult::Contest(ContestResult {
+                attacker: 8.into(),
+                defender: 5.into(),
+                margin: 3.into(),
+                winner: comparison::ContestWinner::Attacker,
+            }))
+        );
+        let tie = |text| match parse_and_roll(text) {
+            Ok(RollResult::Contest(result)) => Some(result.winner),
+            _ => None,
+        };
+        assert_eq!(tie("2d1 vs 2"), Some(comparison::ContestWinner::Defender));
+        assert_eq!(
+            tie("2d1 vs 2 ties attacker"),
+            Some(comparison::ContestWinner::Attacker)
+        );
+    }
+
+    #[test]
+    fn dice_rolled_by_hand_go_through_expressions() {
+        let roll: CompiledRoll = "4d6 k3 + 1d4 >= 12".parse().unwrap();
+        let mut dice = ManualDice::new(vec![1, 2, 3, 4, 3]);
+        assert_eq!(roll.roll(&mut dice), Ok(RollResult::Boolean(true)));
+
+        let mut dice = ManualDice::new(vec![1, 2, 3, 4]);
+        assert_eq!(
+            roll.roll(&mut dice),
+            Err(RollError::Invalid("Ran out of dice"))
+        );
+        let mut dice = ManualDice::new(vec![1, 2, 3, 4, 5]);
+        assert_eq!(
+            roll.roll(&mut dice),
+            Err(RollError::Invalid("A die came up outside its faces"))
+        );
+        assert_eq!(
+            parse_and_roll_symbols_with("2g", ManualDice::new(vec![9])),
+            Err("A die came up outside its faces")
+        );
+    }
+
+    #[test]
+    fn symbol_dice_roll_as_expected() {
+        let result = parse_and_roll_symbols("2g1y2p").unwrap();
+        assert_eq!(result.faces.len(), 5);
+        assert_eq!(result.faces[2].die, "proficiency");
+
+        assert!(parse_and_roll_symbols("2d6").is_err());
+        assert_eq!(
+            parse_and_roll_symbols("2g1y x"),
+            Err("Invalid symbol dice format")
+        );
+    }
+
+    /// The lowest and highest values and the fewest and most dice the roll can
+    /// come to, working through its modifiers in order
+    fn roll_bounds(roll: &ComplexDiceRoll) -> ((i64, i64), (u64, Option<u64>)) {
+        let faces = roll.dice_roll.dice_range as i64;
+        let (mut lowest, mut highest) = (1, faces);
+        let number_of_dice = roll.dice_roll.number_of_dice;
+        let (mut fewest, mut most) = (number_of_dice, Some(number_of_dice));
+        for m in &roll.roll_mods {
+            let count = mod_count(m.value);
+            match m.roll_mod_type {
+                RollModType::E => {
+                    lowest = lowest.min(1);
+                    highest = highest.max(faces);
+                    most = None;
+                }
+                RollModType::R => {
+                    fewest = fewest.saturating_sub(count);
+                    most = most.map(|most| most.saturating_sub(count));
+                }
+                RollModType::K | RollModType::L => {
+                    fewest = fewest.min(count);
+                    most = Some(most.map_or(count, |most| most.min(count)));
+                }
+                RollModType::Min => {
+                    lowest = lowest.max(m.value);
+                    highest = highest.max(m.value);
+                }
+                RollModType::Max => {
+                    lowest = lowest.min(m.value);
+                    highest = highest.min(m.value);
+                }
+                _ => (),
+            }
+        }
+        ((lowest, highest), (fewest, most))
+    }
+
+    proptest! {
+        #[test]
+        fn rolled_dice_stay_within_their_bounds(roll in small_roll(), seed in any::<u64>()) {
+            let rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let (mut request, _) = roll_complex_dice(&roll, rng, EvalLimits::default());
+            // Exploding on every face runs into the depth limit
+            prop_assume!(request.exceeded().is_none());
+            let dice = request.as_slice().unwrap_or_default().to_vec();
+
+            let ((lowest, highest), (fewest, most)) = roll_bounds(&roll);
+            prop_assert!(dice.iter().all(|die| (lowest..=highest).contains(die)), "{:?}", dice);
+            prop_assert!(dice.len() as u64 >= fewest);
+            prop_assert!(most.map_or(true, |most| dice.len() as u64 <= most));
+        }
+
+        #[test]
+        fn random_expressions_roll_without_panicking(
+            expression in small_expression(),
+            seed in any::<u64>(),
+        ) {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let _ = evaluate_with(&expression, &mut rng, &FunctionRegistry::new(), &small_limits());
+        }
+
+        #[test]
+        fn arbitrary_text_never_panics(text in prop_oneof![any::<String>(), token_soup()]) {
+            let _ = parse_and_roll_with(&text, &FunctionRegistry::new(), &small_limits());
+            let _ = parse_and_roll_symbols(&text);
+        }
+    }
+
+    fn small_limits() -> EvalLimits {
+        EvalLimits {
+            max_total_dice: 10_000,
+            max_pooled_dice: 1_000_000,
+            max_total_pooled_dice: 1_000_000,
+            ..EvalLimits::default()
+        }
+    }
 }
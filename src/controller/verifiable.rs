@@ -0,0 +1,234 @@
+extern crate rand_chacha;
+extern crate sha2;
+
+use super::{CompiledRoll, RollResult};
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Marks the hashes that seed verifiable rolls, so they can't be mistaken for
+/// a hash of anything else
+const DOMAIN: &[u8] = b"diceroll verifiable roll v1";
+
+/// The secret half of a verifiable roll. Its commitment is published before any
+/// roll is made with it, and the seed itself is revealed once the rolls are
+/// done, so anyone can check that the rolls came from the seed that was
+/// promised and weren't rolled again until they came out well.
+///
+/// ```
+/// use diceroll::controller::verifiable::{roll_verifiable, verify, ServerSeed};
+///
+/// // The GM publishes the commitment ahead of time
+/// let seed = ServerSeed::generate();
+/// let commitment = seed.commitment();
+///
+/// // A player picks the client seed, and the GM rolls
+/// let result = roll_verifiable("1d20 + 5", &seed, "player's seed", 1).unwrap();
+///
+/// // Once the seed is revealed, anyone can check the roll
+/// let revealed = ServerSeed::from_hex(&seed.to_string()).unwrap();
+/// assert_eq!(
+///     verify("1d20 + 5", &revealed, &commitment, "player's seed", 1, &result),
+///     Ok(())
+/// );
+/// ```
+#[derive(PartialEq, Clone)]
+pub struct ServerSeed([u8; 32]);
+
+impl ServerSeed {
+    /// A new seed from the operating system's random number generator
+    pub fn generate() -> ServerSeed {
+        let mut seed = [0; 32];
+        OsRng.fill_bytes(&mut seed);
+        ServerSeed(seed)
+    }
+
+    pub fn from_bytes(seed: [u8; 32]) -> ServerSeed {
+        ServerSeed(seed)
+    }
+
+    /// Read a seed written out as 64 hexadecimal digits
+    pub fn from_hex(text: &str) -> Result<ServerSeed, &'static str> {
+        let bytes = from_hex(text.trim()).ok_or("Invalid server seed")?;
+        let mut seed = [0; 32];
+        if bytes.len() != seed.len() {
+            return Err("Invalid server seed");
+        }
+        seed.copy_from_slice(&bytes);
+        Ok(ServerSeed(seed))
+    }
+
+    /// The SHA-256 hash of the seed in hexadecimal, to publish before rolling
+    pub fn commitment(&self) -> String {
+        to_hex(&Sha256::digest(&self.0))
+    }
+
+    /// Whether this is the seed that was committed to
+    pub fn matches(&self, commitment: &str) -> bool {
+        self.commitment().eq_ignore_ascii_case(commitment.trim())
+    }
+}
+
+/// The seed is written out in hexadecimal, which reveals it
+impl fmt::Display for ServerSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", to_hex(&self.0))
+    }
+}
+
+/// Only the commitment is shown when debugging, so the seed isn't revealed by
+/// accident
+impl fmt::Debug for ServerSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ServerSeed({})", self.commitment())
+    }
+}
+
+/// The random number generator for a roll, seeded with the SHA-256 hash of
+/// both seeds and the nonce, which numbers the rolls made with the same seeds.
+/// It's ChaCha20, so it gives the same rolls on every platform.
+pub fn verifiable_rng(server_seed: &ServerSeed, client_seed: &str, nonce: u64) -> ChaCha20Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update(server_seed.0);
+    hasher.update(nonce.to_le_bytes());
+    hasher.update(client_seed.as_bytes());
+    let mut seed = [0; 32];
+    seed.copy_from_slice(&hasher.finalize());
+    ChaCha20Rng::from_seed(seed)
+}
+
+/// Roll the expression with the random number generator derived from the seeds
+/// and the nonce
+pub fn roll_verifiable(
+    text: &str,
+    server_seed: &ServerSeed,
+    client_seed: &str,
+    nonce: u64,
+) -> Result<RollResult, &'static str> {
+    let mut rng = verifiable_rng(server_seed, client_seed, nonce);
+    CompiledRoll::new(text)
+        .and_then(|roll| roll.roll(&mut rng))
+        .map_err(|e| e.message())
+}
+
+/// Check that the revealed server seed is the one that was committed to, and
+/// that rolling the expression again with the seeds gives the recorded result
+pub fn verify(
+    text: &str,
+    server_seed: &ServerSeed,
+    commitment: &str,
+    client_seed: &str,
+    nonce: u64,
+    result: &RollResult,
+) -> Result<(), &'static str> {
+    if !server_seed.matches(commitment) {
+        return Err("The server seed doesn't match the commitment");
+    }
+    if roll_verifiable(text, server_seed, client_seed, nonce)? != *result {
+        return Err("The roll doesn't match the recorded result");
+    }
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> ServerSeed {
+        ServerSeed::from_bytes([7; 32])
+    }
+
+    #[test]
+    fn commitments_are_the_hash_of_the_seed() {
+        assert_eq!(seed().commitment(), to_hex(&Sha256::digest(&[7; 32])));
+        assert_eq!(
+            to_hex(&Sha256::digest(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(ServerSeed::from_hex(&seed().to_string()), Ok(seed()));
+        assert_eq!(ServerSeed::from_hex("0707"), Err("Invalid server seed"));
+        assert_eq!(
+            ServerSeed::from_hex(&"zz".repeat(32)),
+            Err("Invalid server seed")
+        );
+        assert!(!format!("{:?}", seed()).contains(&seed().to_string()));
+    }
+
+    #[test]
+    fn verifiable_rolls_are_stable() {
+        // Published rolls have to verify with every later version
+        assert_eq!(
+            seed().commitment(),
+            "4bb06f8e4e3a7715d201d573d0aa423762e55dabd61a2c02278fa56cc6d294e0"
+        );
+        assert_eq!(
+            roll_verifiable("10d20", &seed(), "player", 1),
+            Ok(RollResult::Dice(vec![2, 6, 7, 8, 8, 12, 14, 15, 16, 17]))
+        );
+    }
+
+    #[test]
+    fn rolls_depend_on_both_seeds_and_the_nonce() {
+        let roll =
+            |client_seed: &str, nonce| roll_verifiable("20d100", &seed(), client_seed, nonce);
+        assert_eq!(roll("player", 1), roll("player", 1));
+        assert_ne!(roll("player", 1), roll("player", 2));
+        assert_ne!(roll("player", 1), roll("player2", 1));
+        assert_ne!(
+            roll("player", 1),
+            roll_verifiable("20d100", &ServerSeed::from_bytes([8; 32]), "player", 1)
+        );
+    }
+
+    #[test]
+    fn verification_catches_changed_seeds_and_results() {
+        let commitment = seed().commitment();
+        let result = roll_verifiable("4d6 k3", &seed(), "player", 3).unwrap();
+        assert_eq!(
+            verify(
+                "4d6 k3",
+                &seed(),
+                &commitment.to_uppercase(),
+                "player",
+                3,
+                &result
+            ),
+            Ok(())
+        );
+
+        let other_seed = ServerSeed::from_bytes([8; 32]);
+        assert_eq!(
+            verify("4d6 k3", &other_seed, &commitment, "player", 3, &result),
+            Err("The server seed doesn't match the commitment")
+        );
+        let rerolled = (4..100)
+            .map(|nonce| roll_verifiable("4d6 k3", &seed(), "player", nonce).unwrap())
+            .find(|other| *other != result)
+            .unwrap();
+        assert_eq!(
+            verify("4d6 k3", &seed(), &commitment, "player", 3, &rerolled),
+            Err("The roll doesn't match the recorded result")
+        );
+        assert_eq!(
+            verify("4d6 k2", &seed(), &commitment, "player", 3, &result),
+            Err("The roll doesn't match the recorded result")
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod calculation;
 pub mod controller;
 pub mod parsing;
+pub mod presets;
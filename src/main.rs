@@ -1,7 +1,174 @@
-use diceroll::controller::parse_and_roll_dice;
+use diceroll::calculation::audit::audit;
+use diceroll::calculation::die_source::{DieSource, PromptedDice};
+use diceroll::calculation::rng::{BoxedRng, RngRegistry};
+use diceroll::controller::verifiable::{roll_verifiable, ServerSeed};
+use diceroll::controller::{parse_and_roll_symbols_with, CompiledRoll, RollError, RollResult};
+use std::io;
+use std::process;
+
+const DEFAULT_AUDIT_ROLLS: u64 = 1_000_000;
 
 fn main() {
-    let dice_roll = std::env::args().nth(1).expect("");
-    let v2 = parse_and_roll_dice(&dice_roll).unwrap();
-    println!("{:?}", v2);
-}
\ No newline at end of file
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let manual = take_manual_flag(&mut args);
+    let spec = take_rng_flag(&mut args);
+    let rng: BoxedRng = match &spec {
+        Some(_) if manual => fail("Pick either --rng or --manual"),
+        Some(spec) => RngRegistry::new()
+            .create(spec)
+            .unwrap_or_else(|message| fail(message)),
+        None => Box::new(rand::thread_rng()),
+    };
+    match args.first().map(String::as_str) {
+        Some("audit") if manual => fail("Dice rolled by hand can't be audited"),
+        Some("audit") => run_audit(&args[1..], rng),
+        Some("commit") | Some("verifiable") | Some("verify") if manual => {
+            fail("Dice rolled by hand can't be verified")
+        }
+        // Verifiable rolls derive their generator from the seeds
+        Some("commit") | Some("verifiable") | Some("verify") if spec.is_some() => {
+            fail("Verifiable rolls can't pick a random number generator")
+        }
+        Some("commit") => run_commit(),
+        Some("verifiable") => run_verifiable(&args[1..]),
+        Some("verify") => run_verify(&args[1..]),
+        Some(dice_roll) if manual => roll(
+            dice_roll,
+            PromptedDice::new(io::stdin().lock(), io::stderr()),
+        ),
+        Some(dice_roll) => roll(dice_roll, rng),
+        None => fail("Usage: diceroll [--rng <generator> | --manual] <roll>"),
+    }
+}
+
+/// Take `--manual` out of the arguments. With it, every die is typed in instead
+/// of rolled, for rolling physical dice through the modifiers.
+fn take_manual_flag(args: &mut Vec<String>) -> bool {
+    match args.iter().position(|arg| arg == "--manual") {
+        Some(position) => {
+            args.remove(position);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Take `--rng <name>` or `--rng=<name>` out of the arguments
+fn take_rng_flag(args: &mut Vec<String>) -> Option<String> {
+    let position = args
+        .iter()
+        .position(|arg| arg == "--rng" || arg.starts_with("--rng="))?;
+    let flag = args.remove(position);
+    match flag.strip_prefix("--rng=") {
+        Some(spec) => Some(spec.to_string()),
+        None if position < args.len() => Some(args.remove(position)),
+        None => fail("Usage: --rng <thread|chacha[:seed]|pcg[:seed]|os|replay:values>"),
+    }
+}
+
+fn roll<S: DieSource>(dice_roll: &str, mut source: S) {
+    match CompiledRoll::new(dice_roll).and_then(|roll| roll.roll(&mut source)) {
+        Ok(result) => println!("{}", format_result(&result)),
+        // Text that isn't an expression may still be a pool of symbol dice
+        Err(RollError::Invalid("Invalid dice roll format")) => {
+            match parse_and_roll_symbols_with(dice_roll, source) {
+                Ok(result) => println!("{:?}", result),
+                Err("Invalid symbol dice format") => fail("Invalid dice roll format"),
+                Err(message) => fail(message),
+            }
+        }
+        Err(error) => fail(error.message()),
+    }
+}
+
+/// A result written out the way it's printed, which is also how it's recorded
+/// for `diceroll verify`
+fn format_result(result: &RollResult) -> String {
+    match result {
+        RollResult::Dice(dice) => format!("{:?}", dice),
+        RollResult::Sets(sets) => format!("{:?}", sets),
+        RollResult::Wild(wild_die_roll) => format!("{:?}", wild_die_roll),
+        RollResult::Total(total) => format!("{}", total),
+        RollResult::Boolean(b) => format!("{}", b),
+        RollResult::Contest(contest) => format!("{:?}", contest),
+    }
+}
+
+/// `diceroll commit` makes a server seed to keep secret until the rolls are
+/// done, and the commitment to publish ahead of them
+fn run_commit() {
+    let seed = ServerSeed::generate();
+    println!("Server seed (keep secret): {}", seed);
+    println!("Commitment (publish): {}", seed.commitment());
+}
+
+/// `diceroll verifiable <server seed> <client seed> <nonce> <roll>` rolls with
+/// the random number generator derived from the seeds
+fn run_verifiable(args: &[String]) {
+    let (seed, client_seed, nonce, dice_roll) = match args {
+        [seed, client_seed, nonce, dice_roll] => (seed, client_seed, nonce, dice_roll),
+        _ => fail("Usage: diceroll verifiable <server seed> <client seed> <nonce> <roll>"),
+    };
+    let seed = ServerSeed::from_hex(seed).unwrap_or_else(|message| fail(message));
+    let nonce = parse_nonce(nonce);
+    match roll_verifiable(dice_roll, &seed, client_seed, nonce) {
+        Ok(result) => {
+            println!("Commitment: {}", seed.commitment());
+            println!("{}", format_result(&result));
+        }
+        Err(message) => fail(message),
+    }
+}
+
+/// `diceroll verify <server seed> <commitment> <client seed> <nonce> <roll>
+/// <result>` checks a recorded result against the revealed server seed
+fn run_verify(args: &[String]) {
+    let (seed, commitment, client_seed, nonce, dice_roll, recorded) = match args {
+        [seed, commitment, client_seed, nonce, dice_roll, recorded] => {
+            (seed, commitment, client_seed, nonce, dice_roll, recorded)
+        }
+        _ => fail(
+            "Usage: diceroll verify <server seed> <commitment> <client seed> <nonce> <roll> <result>",
+        ),
+    };
+    let seed = ServerSeed::from_hex(seed).unwrap_or_else(|message| fail(message));
+    if !seed.matches(commitment) {
+        fail("The server seed doesn't match the commitment");
+    }
+    let result = roll_verifiable(dice_roll, &seed, client_seed, parse_nonce(nonce))
+        .unwrap_or_else(|message| fail(message));
+    let without_spaces = |text: &str| text.split_whitespace().collect::<String>();
+    if without_spaces(&format_result(&result)) != without_spaces(recorded) {
+        fail("The roll doesn't match the recorded result");
+    }
+    println!("Verified");
+}
+
+fn parse_nonce(nonce: &str) -> u64 {
+    nonce.parse().unwrap_or_else(|_| fail("Invalid nonce"))
+}
+
+/// `diceroll audit d6 [rolls]` rolls the die and reports how fair it looks
+fn run_audit(args: &[String], rng: BoxedRng) {
+    let dice_range = args
+        .first()
+        .map(|die| die.trim_start_matches(['d', 'D']))
+        .and_then(|faces| faces.parse::<u64>().ok());
+    let rolls = match args.get(1) {
+        Some(rolls) => rolls.replace('_', "").parse::<u64>().ok(),
+        None => Some(DEFAULT_AUDIT_ROLLS),
+    };
+    let (dice_range, rolls) = match (dice_range, rolls) {
+        (Some(dice_range), Some(rolls)) => (dice_range, rolls),
+        _ => fail("Usage: diceroll audit d<faces> [rolls]"),
+    };
+    match audit(rng, dice_range, rolls) {
+        Ok(report) => println!("{}", report),
+        Err(message) => fail(message),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
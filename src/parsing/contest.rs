@@ -0,0 +1,103 @@
+extern crate nom;
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
+    character::complete::{space0, space1},
+    combinator::{map, opt},
+    sequence::{preceded, tuple},
+    IResult,
+};
+
+use super::math_ops::{limited_expression_parse, DEFAULT_MAX_DEPTH};
+use super::{Contest, TieBreak};
+
+/// Parse a contest between two expressions, such as `1d20+7 vs 1d20+4`. Ties go
+/// to the defender, unless the contest ends with `ties attacker` or `ties draw`.
+pub fn contest_parse(input: &str) -> IResult<&str, Contest> {
+    limited_contest_parse(DEFAULT_MAX_DEPTH)(input)
+}
+
+/// Parse a contest like `contest_parse`, limiting how deeply both sides can be nested
+pub fn limited_contest_parse(max_depth: usize) -> impl Fn(&str) -> IResult<&str, Contest> {
+    move |input: &str| {
+        let (input, (attacker, _, _, defender, tie_break)) = tuple((
+            limited_expression_parse(max_depth),
+            space0,
+            tag_no_case("vs"),
+            limited_expression_parse(max_depth),
+            opt(tie_break),
+        ))(input)?;
+        Ok((
+            input,
+            Contest {
+                attacker,
+                defender,
+                tie_break: tie_break.unwrap_or(TieBreak::Defender),
+            },
+        ))
+    }
+}
+
+fn tie_break(input: &str) -> IResult<&str, TieBreak> {
+    preceded(
+        tuple((space0, tag_no_case("ties"), space1)),
+        alt((
+            map(tag_no_case("attacker"), |_| TieBreak::Attacker),
+            map(tag_no_case("defender"), |_| TieBreak::Defender),
+            map(tag_no_case("draw"), |_| TieBreak::Draw),
+        )),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::{ComplexDiceRoll, DiceRoll, Expression, Operation};
+
+    fn d20_plus(bonus: i64) -> Expression {
+        Expression::Operation(
+            Box::new(Expression::Roll(ComplexDiceRoll {
+                dice_roll: DiceRoll {
+                    number_of_dice: 1,
+                    dice_range: 20,
+                },
+                roll_mods: vec![],
+            })),
+            Operation::Add,
+            Box::new(Expression::Number(bonus)),
+        )
+    }
+
+    #[test]
+    fn contest_parses_both_sides() {
+        assert_eq!(
+            contest_parse("1d20+7 vs 1d20+4"),
+            Ok((
+                "",
+                Contest {
+                    attacker: d20_plus(7),
+                    defender: d20_plus(4),
+                    tie_break: TieBreak::Defender,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn contest_parses_tie_break_policy() {
+        assert_eq!(
+            contest_parse("1d20+7 VS 1d20+4 ties attacker").map(|(_, c)| c.tie_break),
+            Ok(TieBreak::Attacker)
+        );
+        assert_eq!(
+            contest_parse("1d20+7 vs 1d20+4 ties draw").map(|(_, c)| c.tie_break),
+            Ok(TieBreak::Draw)
+        );
+    }
+
+    #[test]
+    fn contest_requires_vs() {
+        assert!(contest_parse("1d20+7").is_err());
+    }
+}
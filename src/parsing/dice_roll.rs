@@ -1,14 +1,17 @@
 extern crate nom;
 
 use nom::{
+    branch::alt,
+    bytes::complete::tag_no_case,
     character::complete::{anychar, one_of, space0},
-    combinator::map_res,
+    combinator::{cut, map, map_res, opt, verify},
+    error::ErrorKind,
     multi::many0,
-    sequence::tuple,
+    sequence::{preceded, tuple},
     IResult,
 };
 
-use super::{number, ComplexDiceRoll, DiceRoll, RollMod, RollModType};
+use super::{integer, number, ComplexDiceRoll, DiceRoll, RollMod, RollModType};
 
 pub fn complex_dice_roll_parse(input: &str) -> IResult<&str, ComplexDiceRoll> {
     let (input, _) = space0(input)?;
@@ -26,7 +29,7 @@ pub fn complex_dice_roll_parse(input: &str) -> IResult<&str, ComplexDiceRoll> {
 
 fn simple_dice_roll(input: &str) -> IResult<&str, DiceRoll> {
     let (input, (number_of_dice, _, dice_range)) =
-        tuple((number, dice_roll_separator, number))(input)?;
+        tuple((number, dice_roll_separator, faces))(input)?;
     Ok((
         input,
         DiceRoll {
@@ -38,9 +41,21 @@ fn simple_dice_roll(input: &str) -> IResult<&str, DiceRoll> {
 
 fn dice_roll_mod(input: &str) -> IResult<&str, RollMod> {
     let (input, _) = space0(input)?;
-    let (input, (roll_mod_type, value)) = tuple((roll_type, number))(input)?;
+    alt((valued_dice_roll_mod, wild_dice_roll_mod, flag_dice_roll_mod))(input)
+}
+
+/// Keep and remove modifiers count dice, so their value can't be negative
+fn valued_dice_roll_mod(input: &str) -> IResult<&str, RollMod> {
+    let (rest, (roll_mod_type, value)) = tuple((roll_type, integer))(input)?;
+    let is_count = matches!(
+        roll_mod_type,
+        RollModType::R | RollModType::K | RollModType::L
+    );
+    if is_count && value < 0 {
+        return Err(nom::Err::Failure((input, ErrorKind::Verify)));
+    }
     Ok((
-        input,
+        rest,
         RollMod {
             roll_mod_type,
             value,
@@ -48,8 +63,54 @@ fn dice_roll_mod(input: &str) -> IResult<&str, RollMod> {
     ))
 }
 
+/// A wild die is written as `w`, `w8`, `wild d8` and so on, defaulting to a d6
+fn wild_dice_roll_mod(input: &str) -> IResult<&str, RollMod> {
+    let (input, _) = alt((tag_no_case("wild"), tag_no_case("w")))(input)?;
+    let (input, wild_range) = cut(verify(
+        opt(preceded(opt(dice_roll_separator), number)),
+        |range: &Option<u64>| range.map_or(true, |range| (1..=i64::MAX as u64).contains(&range)),
+    ))(input)?;
+    Ok((
+        input,
+        RollMod {
+            roll_mod_type: RollModType::W,
+            value: wild_range.map_or(6, |range| range as i64),
+        },
+    ))
+}
+
+/// Sort and result mode modifiers don't take a value, so they're stored with a value of 0
+fn flag_dice_roll_mod(input: &str) -> IResult<&str, RollMod> {
+    let (input, roll_mod_type) = alt((
+        map(tag_no_case("sets"), |_| RollModType::Sets),
+        map(tag_no_case("sa"), |_| RollModType::Sa),
+        map(tag_no_case("sd"), |_| RollModType::Sd),
+    ))(input)?;
+    Ok((
+        input,
+        RollMod {
+            roll_mod_type,
+            value: 0,
+        },
+    ))
+}
+
 fn roll_type(input: &str) -> IResult<&str, RollModType> {
-    map_res(anychar, parse_roll_mod_type)(input)
+    alt((
+        map(tag_no_case("min"), |_| RollModType::Min),
+        map(tag_no_case("max"), |_| RollModType::Max),
+        map_res(anychar, parse_roll_mod_type),
+    ))(input)
+}
+
+/// The number of faces on a die. A die with no faces fails the whole parse
+/// instead of being left unparsed.
+fn faces(input: &str) -> IResult<&str, u64> {
+    let (rest, faces) = number(input)?;
+    if faces == 0 {
+        return Err(nom::Err::Failure((input, ErrorKind::Verify)));
+    }
+    Ok((rest, faces))
 }
 
 fn dice_roll_separator(input: &str) -> IResult<&str, char> {
@@ -81,6 +142,39 @@ mod tests {
         assert!(number("a123").is_err())
     }
 
+    #[test]
+    fn dice_need_at_least_one_face() {
+        assert!(complex_dice_roll_parse("2d1").is_ok());
+        for text in ["1d0", "1d00 k1", "1d6 w0", "1d6 wild d0"] {
+            assert!(
+                matches!(complex_dice_roll_parse(text), Err(nom::Err::Failure(_))),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn dice_counts_cant_be_negative() {
+        for text in ["1d6 k-1", "1d6 r-2", "1d6 l-3", "1d6 w18446744073709551615"] {
+            assert!(
+                matches!(complex_dice_roll_parse(text), Err(nom::Err::Failure(_))),
+                "{}",
+                text
+            );
+        }
+        assert!(complex_dice_roll_parse("1d6 k0 min-1 max-1 e-1").is_ok());
+    }
+
+    #[test]
+    fn integer_parser_accepts_a_sign() {
+        assert_eq!(integer(" -12d"), Ok(("d", -12)));
+        assert_eq!(integer("7"), Ok(("", 7)));
+        assert_eq!(integer("-9223372036854775808"), Ok(("", i64::MIN)));
+        assert!(integer("9223372036854775808").is_err());
+        assert!(integer("- 1").is_err());
+    }
+
     #[test]
     fn parse_dice_roll_works_as_expected() {
         assert_eq!(
@@ -164,4 +258,106 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn parse_dice_roll_with_clamp_and_sort_modifications() {
+        assert_eq!(
+            complex_dice_roll_parse("4d6 min3 MAX5 sd k2 sa"),
+            Ok((
+                "",
+                ComplexDiceRoll {
+                    dice_roll: DiceRoll {
+                        number_of_dice: 4,
+                        dice_range: 6
+                    },
+                    roll_mods: vec![
+                        RollMod {
+                            roll_mod_type: RollModType::Min,
+                            value: 3
+                        },
+                        RollMod {
+                            roll_mod_type: RollModType::Max,
+                            value: 5
+                        },
+                        RollMod {
+                            roll_mod_type: RollModType::Sd,
+                            value: 0
+                        },
+                        RollMod {
+                            roll_mod_type: RollModType::K,
+                            value: 2
+                        },
+                        RollMod {
+                            roll_mod_type: RollModType::Sa,
+                            value: 0
+                        }
+                    ],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_dice_roll_with_sets_modification() {
+        assert_eq!(
+            complex_dice_roll_parse("7d10 sets"),
+            Ok((
+                "",
+                ComplexDiceRoll {
+                    dice_roll: DiceRoll {
+                        number_of_dice: 7,
+                        dice_range: 10
+                    },
+                    roll_mods: vec![RollMod {
+                        roll_mod_type: RollModType::Sets,
+                        value: 0
+                    }],
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_dice_roll_with_wild_die() {
+        let wild_die = |value| {
+            vec![RollMod {
+                roll_mod_type: RollModType::W,
+                value,
+            }]
+        };
+
+        assert_eq!(
+            complex_dice_roll_parse("1d8w").map(|(_, r)| r.roll_mods),
+            Ok(wild_die(6))
+        );
+        assert_eq!(
+            complex_dice_roll_parse("1d8 w10").map(|(_, r)| r.roll_mods),
+            Ok(wild_die(10))
+        );
+        assert_eq!(
+            complex_dice_roll_parse("1d8 wild d8").map(|(_, r)| r.roll_mods),
+            Ok(wild_die(8))
+        );
+        assert_eq!(
+            complex_dice_roll_parse("1d8 WILD").map(|(_, r)| r.roll_mods),
+            Ok(wild_die(6))
+        );
+    }
+
+    #[test]
+    fn clamp_modifications_require_a_value() {
+        assert_eq!(
+            complex_dice_roll_parse("4d6 min"),
+            Ok((
+                " min",
+                ComplexDiceRoll {
+                    dice_roll: DiceRoll {
+                        number_of_dice: 4,
+                        dice_range: 6
+                    },
+                    roll_mods: vec![],
+                }
+            ))
+        );
+    }
 }
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use super::{
+    ComplexDiceRoll, Contest, DiceRoll, Expression, Operation, RollMod, RollModType, TieBreak,
+};
+
+/// How tightly each kind of expression binds, from `or` at 0 up to the factors
+/// at 6. A conditional swallows everything after its `else`, so it's put in
+/// parentheses whenever it's an operand.
+fn precedence(expression: &Expression) -> i8 {
+    match expression {
+        Expression::Operation(_, operation, _) => operation_precedence(*operation),
+        Expression::Not(_) => 2,
+        Expression::Conditional(..) => -1,
+        Expression::Roll(_)
+        | Expression::Number(_)
+        | Expression::Negate(_)
+        | Expression::Function(..) => 6,
+    }
+}
+
+fn operation_precedence(operation: Operation) -> i8 {
+    match operation {
+        Operation::Or => 0,
+        Operation::And => 1,
+        Operation::Eq
+        | Operation::Ne
+        | Operation::Lt
+        | Operation::Le
+        | Operation::Gt
+        | Operation::Ge => 3,
+        Operation::Add | Operation::Sub => 4,
+        Operation::Mul | Operation::Div | Operation::ExactDiv | Operation::Mod => 5,
+    }
+}
+
+/// Write the operand, in parentheses when it binds looser than `min_precedence`
+fn operand(f: &mut fmt::Formatter, expression: &Expression, min_precedence: i8) -> fmt::Result {
+    if precedence(expression) < min_precedence {
+        write!(f, "({})", expression)
+    } else {
+        write!(f, "{}", expression)
+    }
+}
+
+/// Expressions are written in a canonical form that parses back to the same
+/// expression: operations are spaced out, dice modifiers are lowercase and
+/// separated by spaces, and parentheses are only used where they're needed.
+/// A negative number is written the way it's typed, so it parses back as the
+/// number negated, and `i64::MIN`, which can't be typed, as `(-i64::MAX - 1)`.
+///
+/// ```
+/// use diceroll::parsing::math_ops::expression_parse;
+///
+/// let (_, expression) = expression_parse(" 4D6K3+(2 )*( 1d4 -1)").unwrap();
+/// assert_eq!(expression.to_string(), "4d6 k3 + 2 * (1d4 - 1)");
+/// ```
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Roll(roll) => write!(f, "{}", roll),
+            Expression::Number(i64::MIN) => write!(f, "(-{} - 1)", i64::MAX),
+            Expression::Number(number) => write!(f, "{}", number),
+            Expression::Operation(first, operation, second) => {
+                let precedence = operation_precedence(*operation);
+                // Comparisons don't chain, so neither side can be a comparison
+                let first_precedence = if precedence == 3 { 4 } else { precedence };
+                operand(f, first, first_precedence)?;
+                write!(f, " {} ", operation)?;
+                operand(f, second, precedence + 1)
+            }
+            Expression::Negate(expression) => {
+                write!(f, "-")?;
+                operand(f, expression, 6)
+            }
+            Expression::Not(expression) => {
+                write!(f, "not ")?;
+                operand(f, expression, 2)
+            }
+            Expression::Function(name, arguments) => {
+                write!(f, "{}(", name)?;
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Conditional(condition, first, second) => {
+                write!(f, "if {} then {} else {}", condition, first, second)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Add => "+",
+            Operation::Sub => "-",
+            Operation::Mul => "*",
+            Operation::Div => "/",
+            Operation::ExactDiv => "//",
+            Operation::Mod => "%",
+            Operation::Eq => "==",
+            Operation::Ne => "!=",
+            Operation::Lt => "<",
+            Operation::Le => "<=",
+            Operation::Gt => ">",
+            Operation::Ge => ">=",
+            Operation::And => "and",
+            Operation::Or => "or",
+        })
+    }
+}
+
+impl fmt::Display for ComplexDiceRoll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.dice_roll)?;
+        for roll_mod in &self.roll_mods {
+            write!(f, " {}", roll_mod)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DiceRoll {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}d{}", self.number_of_dice, self.dice_range)
+    }
+}
+
+impl fmt::Display for RollMod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.roll_mod_type {
+            RollModType::E => write!(f, "e{}", self.value),
+            RollModType::R => write!(f, "r{}", self.value),
+            RollModType::K => write!(f, "k{}", self.value),
+            RollModType::L => write!(f, "l{}", self.value),
+            RollModType::Min => write!(f, "min{}", self.value),
+            RollModType::Max => write!(f, "max{}", self.value),
+            RollModType::W => write!(f, "w{}", self.value),
+            RollModType::Sa => write!(f, "sa"),
+            RollModType::Sd => write!(f, "sd"),
+            RollModType::Sets => write!(f, "sets"),
+        }
+    }
+}
+
+/// Ties go to the defender unless the contest says otherwise, so that's left out
+impl fmt::Display for Contest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} vs {}", self.attacker, self.defender)?;
+        match self.tie_break {
+            TieBreak::Defender => Ok(()),
+            TieBreak::Attacker => write!(f, " ties attacker"),
+            TieBreak::Draw => write!(f, " ties draw"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::contest::contest_parse;
+    use crate::parsing::math_ops::expression_parse;
+    use crate::parsing::strategies::{canonical_form, expression, token_soup};
+    use crate::presets::pbta::{move_expression, PbtaRollMode};
+    use proptest::prelude::*;
+
+    fn canonical(text: &str) -> String {
+        match expression_parse(text) {
+            Ok((rest, expression)) if rest.trim().is_empty() => expression.to_string(),
+            other => panic!("{} didn't parse: {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn expressions_are_normalized() {
+        assert_eq!(canonical(" 23 d  45 "), "23d45");
+        assert_eq!(canonical("4d6K3 + 2"), "4d6 k3 + 2");
+        assert_eq!(canonical("3d6 wild d8 MIN-1 sd"), "3d6 w8 min-1 sd");
+        assert_eq!(canonical("(1 + 2) * 3 - (4 - 5)"), "(1 + 2) * 3 - (4 - 5)");
+        assert_eq!(canonical("((1 * 2)) + (3)"), "1 * 2 + 3");
+        assert_eq!(canonical("-(1d4)+ -(2-1)"), "-1d4 + -(2 - 1)");
+        assert_eq!(canonical("(1 < 2) = (3>=4)"), "(1 < 2) == (3 >= 4)");
+        assert_eq!(canonical("NOT 1d6 and (2 or 3)"), "not 1d6 and (2 or 3)");
+        assert_eq!(
+            canonical("MAX( 1d20,if 1 then 2 else 3 )+(if 1 then 2 else 3)"),
+            "max(1d20, if 1 then 2 else 3) + (if 1 then 2 else 3)"
+        );
+    }
+
+    #[test]
+    fn negative_numbers_parse_back_negated() {
+        let expression = move_expression(-1, PbtaRollMode::Normal);
+        assert_eq!(expression.to_string(), "2d6 + -1");
+        assert_eq!(
+            expression_parse(&expression.to_string()),
+            Ok(("", canonical_form(expression)))
+        );
+
+        let expression = Expression::Number(i64::MIN);
+        assert_eq!(expression.to_string(), "(-9223372036854775807 - 1)");
+        assert_eq!(
+            expression_parse(&expression.to_string()),
+            Ok(("", canonical_form(expression)))
+        );
+    }
+
+    #[test]
+    fn contests_leave_out_the_default_tie_break() {
+        let (_, contest) = contest_parse("1d20+7 VS 1d20+4 ties defender").unwrap();
+        assert_eq!(contest.to_string(), "1d20 + 7 vs 1d20 + 4");
+        let (_, contest) = contest_parse("1d20 vs 1d20 ties draw").unwrap();
+        assert_eq!(contest.to_string(), "1d20 vs 1d20 ties draw");
+    }
+
+    proptest! {
+        #[test]
+        fn formatted_expressions_parse_back(expression in expression()) {
+            let text = expression.to_string();
+            prop_assert_eq!(expression_parse(&text), Ok(("", canonical_form(expression))), "{}", text);
+        }
+
+        #[test]
+        fn parsed_text_formats_back_to_the_same_expression(text in token_soup()) {
+            if let Ok(("", expression)) = expression_parse(&text) {
+                let formatted = expression.to_string();
+                prop_assert_eq!(expression_parse(&formatted), Ok(("", expression)), "{}", text);
+            }
+        }
+
+        #[test]
+        fn formatted_contests_parse_back(
+            attacker in expression(),
+            defender in expression(),
+            tie_break in prop_oneof![
+                Just(TieBreak::Attacker),
+                Just(TieBreak::Defender),
+                Just(TieBreak::Draw),
+            ],
+        ) {
+            let contest = Contest { attacker, defender, tie_break };
+            let text = contest.to_string();
+            let parsed = Contest {
+                attacker: canonical_form(contest.attacker),
+                defender: canonical_form(contest.defender),
+                tie_break,
+            };
+            prop_assert_eq!(contest_parse(&text), Ok(("", parsed)), "{}", text);
+        }
+    }
+}
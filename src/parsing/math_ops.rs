@@ -1,15 +1,233 @@
 extern crate nom;
 
 use nom::{
-    character::complete::{one_of},
-    combinator::map_res,
-    IResult,
+    branch::alt,
+    bytes::complete::{tag, tag_no_case},
+    character::complete::{alpha1, alphanumeric1, char, digit1, one_of, space0},
+    combinator::{map, map_res, not, opt, recognize},
+    error::ErrorKind,
+    multi::{many0, separated_list},
+    sequence::{delimited, pair, preceded, tuple},
+    Err, IResult,
 };
 
-use super::{Operation};
+use super::dice_roll::complex_dice_roll_parse;
+use super::{parse_integer, Expression, Operation};
 
-fn roll_type(input: &str) -> IResult<&str, Operation> {
-    map_res(one_of("+-/*"), parse_operation)(input)
+/// How deeply expressions can be nested when no other limit is given
+pub const DEFAULT_MAX_DEPTH: usize = 100;
+
+/// Parse dice rolls and numbers combined with operations. From the loosest to the
+/// tightest binding, these are `or`, `and`, `not`, the comparisons `==`, `!=`,
+/// `<`, `<=`, `>` and `>=`, then `+` and `-`, then `*`, `/`, `//` and `%`.
+/// A leading `-` negates a factor, so `-1d4` and `2d6 + -3` are allowed.
+/// Parentheses can be used for grouping, `if ... then ... else ...` picks one of
+/// two expressions, and `name(first, second, ...)` calls a function such as
+/// `max` or `floor` with the given arguments.
+///
+/// Expressions can be nested up to `DEFAULT_MAX_DEPTH` levels deep.
+pub fn expression_parse(input: &str) -> IResult<&str, Expression> {
+    limited_expression_parse(DEFAULT_MAX_DEPTH)(input)
+}
+
+/// Parse an expression like `expression_parse`, allowing it to nest `max_depth`
+/// levels deep. Every parenthesis, operation, negation, `not`, conditional and
+/// function call is a level. A deeper expression fails with `ErrorKind::TooLarge`.
+pub fn limited_expression_parse(max_depth: usize) -> impl Fn(&str) -> IResult<&str, Expression> {
+    move |input: &str| or_expression(input, max_depth)
+}
+
+fn or_expression(input: &str, depth: usize) -> IResult<&str, Expression> {
+    fold_chain(
+        input,
+        depth,
+        keyword_operation("or", Operation::Or),
+        and_expression,
+    )
+}
+
+fn and_expression(input: &str, depth: usize) -> IResult<&str, Expression> {
+    fold_chain(
+        input,
+        depth,
+        keyword_operation("and", Operation::And),
+        not_expression,
+    )
+}
+
+fn not_expression(input: &str, depth: usize) -> IResult<&str, Expression> {
+    alt((
+        map(
+            preceded(keyword("not"), |i| nested(i, depth, not_expression)),
+            |expression| Expression::Not(Box::new(expression)),
+        ),
+        |i| comparison(i, depth),
+    ))(input)
+}
+
+/// Comparisons don't chain, so `1 < 2 < 3` stops parsing after `1 < 2`
+fn comparison(input: &str, depth: usize) -> IResult<&str, Expression> {
+    let (input, first) = arithmetic(input, depth)?;
+    let (input, second) = opt(tuple((comparison_operation, |i| {
+        nested(i, depth, arithmetic)
+    })))(input)?;
+    Ok((
+        input,
+        match second {
+            Some(second) => fold_operation(first, second),
+            None => first,
+        },
+    ))
+}
+
+fn arithmetic(input: &str, depth: usize) -> IResult<&str, Expression> {
+    fold_chain(input, depth, operation_of("+-"), term)
+}
+
+fn term(input: &str, depth: usize) -> IResult<&str, Expression> {
+    fold_chain(
+        input,
+        depth,
+        alt((
+            preceded(space0, map(tag("//"), |_| Operation::ExactDiv)),
+            operation_of("*/%"),
+        )),
+        factor,
+    )
+}
+
+/// Parse operands separated by operations, from left to right. Every operation
+/// nests the expression one level deeper.
+fn fold_chain<'a, O>(
+    input: &'a str,
+    depth: usize,
+    operation: O,
+    operand: fn(&'a str, usize) -> IResult<&'a str, Expression>,
+) -> IResult<&'a str, Expression>
+where
+    O: Fn(&'a str) -> IResult<&'a str, Operation>,
+{
+    let (mut input, mut expression) = operand(input, depth)?;
+    let mut depth = depth;
+    loop {
+        let (rest, operation) = match operation(input) {
+            Ok(parsed) => parsed,
+            Err(Err::Error(_)) => return Ok((input, expression)),
+            Err(e) => return Err(e),
+        };
+        depth = match depth.checked_sub(1) {
+            Some(depth) => depth,
+            None => return Err(Err::Failure((input, ErrorKind::TooLarge))),
+        };
+        match operand(rest, depth) {
+            Ok((rest, second)) => {
+                expression = fold_operation(expression, (operation, second));
+                input = rest;
+            }
+            Err(Err::Error(_)) => return Ok((input, expression)),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn factor(input: &str, depth: usize) -> IResult<&str, Expression> {
+    let (input, _) = space0(input)?;
+    alt((
+        delimited(
+            char('('),
+            |i| nested(i, depth, or_expression),
+            preceded(space0, char(')')),
+        ),
+        |i| conditional(i, depth),
+        |i| function_call(i, depth),
+        map(complex_dice_roll_parse, Expression::Roll),
+        map(map_res(digit1, parse_integer), Expression::Number),
+        map(
+            preceded(char('-'), |i| nested(i, depth, factor)),
+            |expression| Expression::Negate(Box::new(expression)),
+        ),
+    ))(input)
+}
+
+fn conditional(input: &str, depth: usize) -> IResult<&str, Expression> {
+    let (input, (_, condition, _, first, _, second)) = tuple((
+        keyword("if"),
+        |i| nested(i, depth, or_expression),
+        keyword("then"),
+        |i| nested(i, depth, or_expression),
+        keyword("else"),
+        |i| nested(i, depth, or_expression),
+    ))(input)?;
+    Ok((
+        input,
+        Expression::Conditional(Box::new(condition), Box::new(first), Box::new(second)),
+    ))
+}
+
+fn function_call(input: &str, depth: usize) -> IResult<&str, Expression> {
+    let (input, (name, _, arguments, _, _)) = tuple((
+        recognize(pair(alpha1, many0(alt((alphanumeric1, tag("_")))))),
+        preceded(space0, char('(')),
+        separated_list(preceded(space0, char(',')), |i| {
+            nested(i, depth, or_expression)
+        }),
+        space0,
+        char(')'),
+    ))(input)?;
+    Ok((input, Expression::Function(name.to_lowercase(), arguments)))
+}
+
+/// Parse one level deeper, failing without backtracking when there are no
+/// levels left
+fn nested<'a>(
+    input: &'a str,
+    depth: usize,
+    parser: fn(&'a str, usize) -> IResult<&'a str, Expression>,
+) -> IResult<&'a str, Expression> {
+    match depth.checked_sub(1) {
+        Some(depth) => parser(input, depth),
+        None => Err(Err::Failure((input, ErrorKind::TooLarge))),
+    }
+}
+
+fn fold_operation(first: Expression, (operation, second): (Operation, Expression)) -> Expression {
+    Expression::Operation(Box::new(first), operation, Box::new(second))
+}
+
+fn operation_of(operations: &'static str) -> impl Fn(&str) -> IResult<&str, Operation> {
+    move |input: &str| {
+        let (input, _) = space0(input)?;
+        map_res(one_of(operations), parse_operation)(input)
+    }
+}
+
+fn comparison_operation(input: &str) -> IResult<&str, Operation> {
+    let (input, _) = space0(input)?;
+    alt((
+        map(tag(">="), |_| Operation::Ge),
+        map(tag("<="), |_| Operation::Le),
+        map(tag("=="), |_| Operation::Eq),
+        map(tag("!="), |_| Operation::Ne),
+        map(tag(">"), |_| Operation::Gt),
+        map(tag("<"), |_| Operation::Lt),
+        map(tag("="), |_| Operation::Eq),
+    ))(input)
+}
+
+fn keyword_operation(
+    word: &'static str,
+    operation: Operation,
+) -> impl Fn(&str) -> IResult<&str, Operation> {
+    move |input: &str| map(keyword(word), |_| operation)(input)
+}
+
+/// A whole word, so that names like `nothing` aren't read as `not hing`
+fn keyword(word: &'static str) -> impl Fn(&str) -> IResult<&str, &str> {
+    move |input: &str| {
+        let (input, word) = preceded(space0, tag_no_case(word))(input)?;
+        let (input, _) = not(alt((alphanumeric1, tag("_"))))(input)?;
+        Ok((input, word))
+    }
 }
 
 fn parse_operation(op_char: char) -> Result<Operation, &'static str> {
@@ -18,13 +236,25 @@ fn parse_operation(op_char: char) -> Result<Operation, &'static str> {
         '-' => Ok(Operation::Sub),
         '/' => Ok(Operation::Div),
         '*' => Ok(Operation::Mul),
-        _ => Err("Invalid Operation type. Expected one of +, -, /, or *"),
+        '%' => Ok(Operation::Mod),
+        _ => Err("Invalid Operation type. Expected one of +, -, /, *, or %"),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::parsing::{ComplexDiceRoll, DiceRoll};
+
+    fn roll(number_of_dice: u64, dice_range: u64) -> Box<Expression> {
+        Box::new(Expression::Roll(ComplexDiceRoll {
+            dice_roll: DiceRoll {
+                number_of_dice,
+                dice_range,
+            },
+            roll_mods: vec![],
+        }))
+    }
 
     #[test]
     fn operation_parses_as_expected() {
@@ -33,6 +263,287 @@ mod tests {
 
     #[test]
     fn op_parser_parses_operation() {
-        assert_eq!(roll_type("/abcd"), Ok(("abcd", Operation::Div)))
+        assert_eq!(operation_of("+-/*")("/abcd"), Ok(("abcd", Operation::Div)))
+    }
+
+    #[test]
+    fn expression_parses_flat_modifier() {
+        assert_eq!(
+            expression_parse("2d6 + 3"),
+            Ok((
+                "",
+                Expression::Operation(roll(2, 6), Operation::Add, Box::new(Expression::Number(3)))
+            ))
+        );
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(
+            expression_parse("1d4+2*1d6"),
+            Ok((
+                "",
+                Expression::Operation(
+                    roll(1, 4),
+                    Operation::Add,
+                    Box::new(Expression::Operation(
+                        Box::new(Expression::Number(2)),
+                        Operation::Mul,
+                        roll(1, 6)
+                    ))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn parentheses_group_operations() {
+        assert_eq!(
+            expression_parse("(1d4 + 2) * 3"),
+            Ok((
+                "",
+                Expression::Operation(
+                    Box::new(Expression::Operation(
+                        roll(1, 4),
+                        Operation::Add,
+                        Box::new(Expression::Number(2))
+                    )),
+                    Operation::Mul,
+                    Box::new(Expression::Number(3))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn subtraction_is_left_associative() {
+        assert_eq!(
+            expression_parse("10 - 2 - 3"),
+            Ok((
+                "",
+                Expression::Operation(
+                    Box::new(Expression::Operation(
+                        Box::new(Expression::Number(10)),
+                        Operation::Sub,
+                        Box::new(Expression::Number(2))
+                    )),
+                    Operation::Sub,
+                    Box::new(Expression::Number(3))
+                )
+            ))
+        );
+    }
+
+    fn number(number: i64) -> Box<Expression> {
+        Box::new(Expression::Number(number))
+    }
+
+    #[test]
+    fn comparison_binds_looser_than_arithmetic() {
+        assert_eq!(
+            expression_parse("1d20+5 >= 15"),
+            Ok((
+                "",
+                Expression::Operation(
+                    Box::new(Expression::Operation(
+                        roll(1, 20),
+                        Operation::Add,
+                        number(5)
+                    )),
+                    Operation::Ge,
+                    number(15)
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn comparison_operations_parse_as_expected() {
+        let operation = |text| match expression_parse(text) {
+            Ok(("", Expression::Operation(_, operation, _))) => Some(operation),
+            _ => None,
+        };
+
+        assert_eq!(operation("1 == 2"), Some(Operation::Eq));
+        assert_eq!(operation("1 = 2"), Some(Operation::Eq));
+        assert_eq!(operation("1 != 2"), Some(Operation::Ne));
+        assert_eq!(operation("1 < 2"), Some(Operation::Lt));
+        assert_eq!(operation("1 <= 2"), Some(Operation::Le));
+        assert_eq!(operation("1 > 2"), Some(Operation::Gt));
+        assert_eq!(operation("1 >= 2"), Some(Operation::Ge));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            expression_parse("1 or not 0 and 2"),
+            Ok((
+                "",
+                Expression::Operation(
+                    number(1),
+                    Operation::Or,
+                    Box::new(Expression::Operation(
+                        Box::new(Expression::Not(number(0))),
+                        Operation::And,
+                        number(2)
+                    ))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn conditional_parses_all_three_parts() {
+        assert_eq!(
+            expression_parse("if 1d20 >= 15 then 2d6 else 1d6"),
+            Ok((
+                "",
+                Expression::Conditional(
+                    Box::new(Expression::Operation(
+                        roll(1, 20),
+                        Operation::Ge,
+                        number(15)
+                    )),
+                    roll(2, 6),
+                    roll(1, 6)
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn conditional_requires_an_else() {
+        assert!(expression_parse("if 1 then 2d6").is_err());
+    }
+
+    #[test]
+    fn exact_division_and_modulo_parse_as_expected() {
+        assert_eq!(
+            expression_parse("3d6 // 2 % 4"),
+            Ok((
+                "",
+                Expression::Operation(
+                    Box::new(Expression::Operation(
+                        roll(3, 6),
+                        Operation::ExactDiv,
+                        number(2)
+                    )),
+                    Operation::Mod,
+                    number(4)
+                )
+            ))
+        );
+        assert_eq!(
+            expression_parse("3d6/2"),
+            Ok((
+                "",
+                Expression::Operation(roll(3, 6), Operation::Div, number(2))
+            ))
+        );
+    }
+
+    #[test]
+    fn unary_minus_negates_a_factor() {
+        assert_eq!(
+            expression_parse("-1d4"),
+            Ok(("", Expression::Negate(roll(1, 4))))
+        );
+        assert_eq!(
+            expression_parse("2d6 + -3"),
+            Ok((
+                "",
+                Expression::Operation(
+                    roll(2, 6),
+                    Operation::Add,
+                    Box::new(Expression::Negate(number(3)))
+                )
+            ))
+        );
+        assert_eq!(
+            expression_parse("2--3*2"),
+            Ok((
+                "",
+                Expression::Operation(
+                    number(2),
+                    Operation::Sub,
+                    Box::new(Expression::Operation(
+                        Box::new(Expression::Negate(number(3))),
+                        Operation::Mul,
+                        number(2)
+                    ))
+                )
+            ))
+        );
+        assert_eq!(
+            expression_parse("-(1 + 2)"),
+            Ok((
+                "",
+                Expression::Negate(Box::new(Expression::Operation(
+                    number(1),
+                    Operation::Add,
+                    number(2)
+                )))
+            ))
+        );
+        assert_eq!(
+            expression_parse("- -1"),
+            Ok((
+                "",
+                Expression::Negate(Box::new(Expression::Negate(number(1))))
+            ))
+        );
+    }
+
+    #[test]
+    fn function_calls_parse_as_expected() {
+        assert_eq!(
+            expression_parse("max(3d6 // 2, 4) + 1"),
+            Ok((
+                "",
+                Expression::Operation(
+                    Box::new(Expression::Function(
+                        String::from("max"),
+                        vec![
+                            Expression::Operation(roll(3, 6), Operation::ExactDiv, number(2)),
+                            Expression::Number(4)
+                        ]
+                    )),
+                    Operation::Add,
+                    number(1)
+                )
+            ))
+        );
+        assert_eq!(
+            expression_parse("FLOOR( 1 )"),
+            Ok((
+                "",
+                Expression::Function(String::from("floor"), vec![Expression::Number(1)])
+            ))
+        );
+        assert_eq!(
+            expression_parse("my_func2()"),
+            Ok(("", Expression::Function(String::from("my_func2"), vec![])))
+        );
+        assert!(expression_parse("round 1").is_err());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        assert!(limited_expression_parse(3)("((1))").is_ok());
+        assert!(limited_expression_parse(3)("max(-(1))").is_ok());
+        assert_eq!(
+            limited_expression_parse(2)("max(-(1))"),
+            Err(Err::Failure(("1))", ErrorKind::TooLarge)))
+        );
+        assert!(limited_expression_parse(2)("not not not 1").is_err());
+        assert!(limited_expression_parse(2)("1 + 2 * 3 - 4").is_ok());
+        assert!(limited_expression_parse(2)("1 + 2 - 3 + 4").is_err());
+
+        let deep = format!("{}1{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert!(expression_parse(&deep).is_err());
+        let long = format!("1{}", "+1".repeat(100_000));
+        assert!(expression_parse(&long).is_err());
+        let nested = format!("{}1{}", "(".repeat(99), ")".repeat(99));
+        assert!(expression_parse(&nested).is_ok());
     }
 }
@@ -1,17 +1,50 @@
+pub mod contest;
 pub mod dice_roll;
-
-// TODO: Remove after implementing math operations to dice rolls
-#[allow(dead_code)]
+mod display;
 pub mod math_ops;
+#[cfg(test)]
+pub(crate) mod strategies;
+pub mod symbol_dice;
 
 extern crate nom;
 
 use nom::{
-    character::complete::{digit1, space0},
-    combinator::map_res,
+    character::complete::{char, digit1, space0},
+    combinator::{map_res, opt, recognize},
+    sequence::pair,
     IResult,
 };
 
+/// Dice rolls and numbers combined with arithmetic, comparison and logical operations
+#[derive(PartialEq, Debug, Clone)]
+pub enum Expression {
+    Roll(ComplexDiceRoll),
+    Number(i64),
+    Operation(Box<Expression>, Operation, Box<Expression>),
+    /// Unary minus, such as `-1d4`
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    /// A call such as `max(1d20, 10)`, looked up by name when it's evaluated
+    Function(String, Vec<Expression>),
+    /// `if condition then first else second`
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+/// Two expressions rolled against each other, such as `1d20+7 vs 1d20+4`
+#[derive(PartialEq, Debug, Clone)]
+pub struct Contest {
+    pub attacker: Expression,
+    pub defender: Expression,
+    pub tie_break: TieBreak,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TieBreak {
+    Attacker,
+    Defender,
+    Draw,
+}
+
 #[derive(Debug, Clone)]
 pub struct ComplexDiceRoll {
     pub dice_roll: DiceRoll,
@@ -27,7 +60,7 @@ pub struct DiceRoll {
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub struct RollMod {
     pub roll_mod_type: RollModType,
-    pub value: u64,
+    pub value: i64,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -36,14 +69,47 @@ pub enum RollModType {
     R,
     K,
     L,
+    Min,
+    Max,
+    Sa,
+    Sd,
+    Sets,
+    W,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SymbolDiceRoll {
+    pub number_of_dice: u64,
+    pub die_type: SymbolDieType,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SymbolDieType {
+    Boost,
+    Setback,
+    Ability,
+    Difficulty,
+    Proficiency,
+    Challenge,
+    Force,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
-enum Operation {
+pub enum Operation {
     Add,
     Mul,
     Div,
+    ExactDiv,
+    Mod,
     Sub,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
 }
 
 impl PartialEq<ComplexDiceRoll> for ComplexDiceRoll {
@@ -55,10 +121,20 @@ impl PartialEq<ComplexDiceRoll> for ComplexDiceRoll {
 }
 
 fn parse_number(text: &str) -> Result<u64, std::num::ParseIntError> {
-    u64::from_str_radix(text, 10)
+    text.parse::<u64>()
 }
 
 fn number(input: &str) -> IResult<&str, u64> {
     let (input, _) = space0(input)?;
     map_res(digit1, parse_number)(input)
 }
+
+fn parse_integer(text: &str) -> Result<i64, std::num::ParseIntError> {
+    text.parse::<i64>()
+}
+
+/// An integer with an optional minus sign
+fn integer(input: &str) -> IResult<&str, i64> {
+    let (input, _) = space0(input)?;
+    map_res(recognize(pair(opt(char('-')), digit1)), parse_integer)(input)
+}
@@ -0,0 +1,199 @@
+//! Generators of random expressions for property tests
+
+use super::{ComplexDiceRoll, DiceRoll, Expression, Operation, RollMod, RollModType};
+use proptest::prelude::*;
+
+fn valued_mod_type() -> impl Strategy<Value = RollModType> {
+    prop_oneof![
+        Just(RollModType::E),
+        Just(RollModType::Min),
+        Just(RollModType::Max),
+    ]
+}
+
+/// Modifiers whose value is a number of dice, which can't be negative
+fn count_mod_type() -> impl Strategy<Value = RollModType> {
+    prop_oneof![
+        Just(RollModType::R),
+        Just(RollModType::K),
+        Just(RollModType::L),
+    ]
+}
+
+fn flag_mod_type() -> impl Strategy<Value = RollModType> {
+    prop_oneof![
+        Just(RollModType::Sa),
+        Just(RollModType::Sd),
+        Just(RollModType::Sets),
+    ]
+}
+
+fn flag_mod() -> impl Strategy<Value = RollMod> {
+    flag_mod_type().prop_map(|roll_mod_type| RollMod {
+        roll_mod_type,
+        value: 0,
+    })
+}
+
+/// Any modifier the parser accepts. Wild dice have at least one face.
+pub(crate) fn roll_mod() -> impl Strategy<Value = RollMod> {
+    prop_oneof![
+        (valued_mod_type(), any::<i64>()).prop_map(|(roll_mod_type, value)| RollMod {
+            roll_mod_type,
+            value
+        }),
+        (count_mod_type(), 0..=i64::MAX).prop_map(|(roll_mod_type, value)| RollMod {
+            roll_mod_type,
+            value
+        }),
+        (1..=i64::MAX).prop_map(|value| RollMod {
+            roll_mod_type: RollModType::W,
+            value
+        }),
+        flag_mod(),
+    ]
+}
+
+/// Any roll the parser accepts, which is far too big to roll
+pub(crate) fn roll() -> impl Strategy<Value = ComplexDiceRoll> {
+    (
+        any::<u64>(),
+        1..=u64::MAX,
+        prop::collection::vec(roll_mod(), 0..4),
+    )
+        .prop_map(|(number_of_dice, dice_range, roll_mods)| ComplexDiceRoll {
+            dice_roll: DiceRoll {
+                number_of_dice,
+                dice_range,
+            },
+            roll_mods,
+        })
+}
+
+/// Rolls small enough to roll quickly, with modifier values that land near the
+/// faces of the dice. Wild dice are left out, since they replace the roll.
+pub(crate) fn small_roll() -> impl Strategy<Value = ComplexDiceRoll> {
+    let roll_mod = prop_oneof![
+        (valued_mod_type(), -3i64..30).prop_map(|(roll_mod_type, value)| RollMod {
+            roll_mod_type,
+            value
+        }),
+        (count_mod_type(), 0i64..30).prop_map(|(roll_mod_type, value)| RollMod {
+            roll_mod_type,
+            value
+        }),
+        flag_mod(),
+    ];
+    (0u64..30, 1u64..=20, prop::collection::vec(roll_mod, 0..4)).prop_map(
+        |(number_of_dice, dice_range, roll_mods)| ComplexDiceRoll {
+            dice_roll: DiceRoll {
+                number_of_dice,
+                dice_range,
+            },
+            roll_mods,
+        },
+    )
+}
+
+pub(crate) fn operation() -> impl Strategy<Value = Operation> {
+    prop_oneof![
+        Just(Operation::Add),
+        Just(Operation::Sub),
+        Just(Operation::Mul),
+        Just(Operation::Div),
+        Just(Operation::ExactDiv),
+        Just(Operation::Mod),
+        Just(Operation::Eq),
+        Just(Operation::Ne),
+        Just(Operation::Lt),
+        Just(Operation::Le),
+        Just(Operation::Gt),
+        Just(Operation::Ge),
+        Just(Operation::And),
+        Just(Operation::Or),
+    ]
+}
+
+/// Expressions built out of the given rolls and numbers, with function names in
+/// lowercase the way the parser gives them
+pub(crate) fn expression_of(
+    roll: impl Strategy<Value = ComplexDiceRoll> + 'static,
+    number: impl Strategy<Value = i64> + 'static,
+) -> impl Strategy<Value = Expression> {
+    let leaf = prop_oneof![
+        roll.prop_map(Expression::Roll),
+        number.prop_map(Expression::Number),
+    ];
+    leaf.prop_recursive(6, 48, 3, |inner| {
+        prop_oneof![
+            (inner.clone(), operation(), inner.clone())
+                .prop_map(|(a, op, b)| { Expression::Operation(Box::new(a), op, Box::new(b)) }),
+            inner.clone().prop_map(|e| Expression::Negate(Box::new(e))),
+            inner.clone().prop_map(|e| Expression::Not(Box::new(e))),
+            (
+                prop_oneof![Just("max"), Just("sum"), Just("floor"), Just("my_func2")],
+                prop::collection::vec(inner.clone(), 0..3)
+            )
+                .prop_map(|(name, arguments)| Expression::Function(name.to_string(), arguments)),
+            (inner.clone(), inner.clone(), inner).prop_map(|(c, a, b)| {
+                Expression::Conditional(Box::new(c), Box::new(a), Box::new(b))
+            }),
+        ]
+    })
+}
+
+/// Any expression, including negative numbers that the parser only gives as
+/// negated ones
+pub(crate) fn expression() -> impl Strategy<Value = Expression> {
+    expression_of(roll(), any::<i64>())
+}
+
+/// The expression the parser gives back for the formatted `expression`, where
+/// every negative number is a positive one negated
+pub(crate) fn canonical_form(expression: Expression) -> Expression {
+    let boxed = |expression: Box<Expression>| Box::new(canonical_form(*expression));
+    match expression {
+        Expression::Number(i64::MIN) => Expression::Operation(
+            Box::new(Expression::Negate(Box::new(Expression::Number(i64::MAX)))),
+            Operation::Sub,
+            Box::new(Expression::Number(1)),
+        ),
+        Expression::Number(number) if number < 0 => {
+            Expression::Negate(Box::new(Expression::Number(-number)))
+        }
+        Expression::Operation(first, operation, second) => {
+            Expression::Operation(boxed(first), operation, boxed(second))
+        }
+        Expression::Negate(expression) => Expression::Negate(boxed(expression)),
+        Expression::Not(expression) => Expression::Not(boxed(expression)),
+        Expression::Function(name, arguments) => {
+            Expression::Function(name, arguments.into_iter().map(canonical_form).collect())
+        }
+        Expression::Conditional(condition, first, second) => {
+            Expression::Conditional(boxed(condition), boxed(first), boxed(second))
+        }
+        expression => expression,
+    }
+}
+
+/// Expressions small enough to roll quickly
+pub(crate) fn small_expression() -> impl Strategy<Value = Expression> {
+    expression_of(small_roll(), 0i64..100)
+}
+
+/// Text made of the words and symbols of the grammar, which gets much further
+/// into the parser than arbitrary text does
+pub(crate) fn token_soup() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        "[0-9]{1,3}",
+        "[0-9]{18,21}",
+        prop::sample::select(vec![
+            "d", "D", "k", "l", "e", "r", "w", "wild", "min", "max", "sa", "sd", "sets", "(", ")",
+            "+", "-", "*", "/", "//", "%", "==", "=", "!=", "<", "<=", ">", ">=", "and", "or",
+            "not", "if", "then", "else", "vs", "ties", "attacker", "defender", "draw", ",", "sum",
+            "floor", " ", "  ",
+        ])
+        .prop_map(str::to_string),
+    ];
+    prop::collection::vec(token, 0..24).prop_map(|tokens| tokens.concat())
+}
@@ -0,0 +1,101 @@
+extern crate nom;
+
+use nom::{
+    character::complete::{anychar, space0},
+    combinator::{map_res, opt},
+    multi::many1,
+    sequence::tuple,
+    IResult,
+};
+
+use super::{number, SymbolDiceRoll, SymbolDieType};
+
+/// Parse a pool of symbol dice such as `2g1y2p`, where each die colour can be
+/// preceded by the number of dice of that colour to roll
+pub fn symbol_dice_pool_parse(input: &str) -> IResult<&str, Vec<SymbolDiceRoll>> {
+    many1(symbol_dice_roll)(input)
+}
+
+fn symbol_dice_roll(input: &str) -> IResult<&str, SymbolDiceRoll> {
+    let (input, _) = space0(input)?;
+    let (input, (number_of_dice, die_type)) = tuple((opt(number), symbol_die_type))(input)?;
+    Ok((
+        input,
+        SymbolDiceRoll {
+            number_of_dice: number_of_dice.unwrap_or(1),
+            die_type,
+        },
+    ))
+}
+
+fn symbol_die_type(input: &str) -> IResult<&str, SymbolDieType> {
+    let (input, _) = space0(input)?;
+    map_res(anychar, parse_symbol_die_type)(input)
+}
+
+fn parse_symbol_die_type(c: char) -> Result<SymbolDieType, &'static str> {
+    match &c.to_lowercase().next().unwrap() {
+        'b' => Ok(SymbolDieType::Boost),
+        'k' => Ok(SymbolDieType::Setback),
+        'g' => Ok(SymbolDieType::Ability),
+        'p' => Ok(SymbolDieType::Difficulty),
+        'y' => Ok(SymbolDieType::Proficiency),
+        'r' => Ok(SymbolDieType::Challenge),
+        'w' => Ok(SymbolDieType::Force),
+        _ => Err("Invalid Symbol Die Type"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbol_dice_pool_parses_as_expected() {
+        assert_eq!(
+            symbol_dice_pool_parse("2g1y 2P"),
+            Ok((
+                "",
+                vec![
+                    SymbolDiceRoll {
+                        number_of_dice: 2,
+                        die_type: SymbolDieType::Ability
+                    },
+                    SymbolDiceRoll {
+                        number_of_dice: 1,
+                        die_type: SymbolDieType::Proficiency
+                    },
+                    SymbolDiceRoll {
+                        number_of_dice: 2,
+                        die_type: SymbolDieType::Difficulty
+                    },
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn number_of_symbol_dice_defaults_to_one() {
+        assert_eq!(
+            symbol_dice_pool_parse("bk"),
+            Ok((
+                "",
+                vec![
+                    SymbolDiceRoll {
+                        number_of_dice: 1,
+                        die_type: SymbolDieType::Boost
+                    },
+                    SymbolDiceRoll {
+                        number_of_dice: 1,
+                        die_type: SymbolDieType::Setback
+                    },
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn numeric_dice_are_not_symbol_dice() {
+        assert!(symbol_dice_pool_parse("2d6").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+extern crate rand;
+
+use super::dice;
+use crate::calculation::outcomes::OutcomeInterpreter;
+use crate::calculation::RollRequest;
+use rand::Rng;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BladesOutcome {
+    Critical,
+    FullSuccess,
+    PartialSuccess,
+    Failure,
+}
+
+/// Reads a Blades in the Dark action roll from the highest die in the pool:
+/// two or more 6s is a critical, a 6 a full success, 4-5 a partial success and
+/// 1-3 a failure. An empty pool is a failure.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct BladesAction;
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct BladesRoll {
+    pub dice: Vec<i64>,
+    pub outcome: BladesOutcome,
+}
+
+impl OutcomeInterpreter for BladesAction {
+    type Outcome = BladesOutcome;
+
+    fn interpret(&self, dice: &[i64]) -> BladesOutcome {
+        match dice.iter().filter(|x| **x == 6).count() {
+            0 => match dice.iter().max() {
+                Some(4) | Some(5) => BladesOutcome::PartialSuccess,
+                _ => BladesOutcome::Failure,
+            },
+            1 => BladesOutcome::FullSuccess,
+            _ => BladesOutcome::Critical,
+        }
+    }
+}
+
+/// Roll a pool of `pool` d6s and read the action roll. A zero dice pool rolls
+/// 2d6 and keeps the lowest, so it can never be a critical. The request is
+/// started over first, so every roll is held to the limits on its own.
+///
+/// ```
+/// use diceroll::calculation::RollRequest;
+/// use diceroll::presets::blades::action_roll;
+/// let mut request = RollRequest::new(rand::thread_rng());
+///
+/// let roll = action_roll(&mut request, 0).unwrap();
+/// assert_eq!(roll.dice.len(), 1);
+/// ```
+pub fn action_roll<R: Rng>(
+    request: &mut RollRequest<R>,
+    pool: u64,
+) -> Result<BladesRoll, &'static str> {
+    request.reset();
+    if pool == 0 {
+        request.roll_dice(2, 6).keep_lower(1);
+    } else {
+        request.roll_dice(pool, 6);
+    }
+    let dice = dice(request)?;
+    let outcome = BladesAction.interpret(&dice);
+    Ok(BladesRoll { dice, outcome })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blades_outcomes_read_the_highest_die() {
+        assert_eq!(BladesAction.interpret(&[1, 6, 6]), BladesOutcome::Critical);
+        assert_eq!(
+            BladesAction.interpret(&[3, 6, 5]),
+            BladesOutcome::FullSuccess
+        );
+        assert_eq!(
+            BladesAction.interpret(&[4, 1]),
+            BladesOutcome::PartialSuccess
+        );
+        assert_eq!(BladesAction.interpret(&[5]), BladesOutcome::PartialSuccess);
+        assert_eq!(BladesAction.interpret(&[3, 2, 1]), BladesOutcome::Failure);
+        assert_eq!(BladesAction.interpret(&[]), BladesOutcome::Failure);
+    }
+
+    #[test]
+    fn zero_dice_pool_keeps_the_lowest_of_two() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+
+        assert_eq!(
+            action_roll(&mut request, 0),
+            Ok(BladesRoll {
+                dice: vec![1],
+                outcome: BladesOutcome::Failure
+            })
+        );
+        assert_eq!(action_roll(&mut request, 3).unwrap().dice, vec![1, 1, 1]);
+        assert_eq!(
+            action_roll(&mut request, u64::MAX),
+            Err("Too many dice in one roll")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+extern crate rand;
+
+use super::dice;
+use crate::calculation::RollRequest;
+use rand::Rng;
+
+/// A check can't have more than two bonus or two penalty dice
+pub const MAX_BONUS_DICE: i64 = 2;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SuccessLevel {
+    Critical,
+    Extreme,
+    Hard,
+    Regular,
+    Failure,
+    Fumble,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct PercentileCheck {
+    pub roll: i64,
+    pub tens_dice: Vec<i64>,
+    pub units_die: i64,
+    pub level: SuccessLevel,
+}
+
+impl SuccessLevel {
+    /// Read a d100 roll against `skill`. A roll of 01 is always a critical, and
+    /// 100 is always a fumble. Below a skill of 50, 96-99 are fumbles too.
+    pub fn from_roll(roll: i64, skill: i64) -> SuccessLevel {
+        let fumble = roll == 100 || (skill < 50 && roll >= 96);
+        match roll {
+            1 => SuccessLevel::Critical,
+            _ if fumble => SuccessLevel::Fumble,
+            r if r <= skill / 5 => SuccessLevel::Extreme,
+            r if r <= skill / 2 => SuccessLevel::Hard,
+            r if r <= skill => SuccessLevel::Regular,
+            _ => SuccessLevel::Failure,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        !matches!(self, SuccessLevel::Failure | SuccessLevel::Fumble)
+    }
+}
+
+/// Make a Call of Cthulhu percentile check against `skill`. A positive
+/// `bonus_dice` rolls that many extra tens dice and keeps the best result, a
+/// negative one rolls extra penalty tens dice and keeps the worst. There are
+/// never more than `MAX_BONUS_DICE` of either. The request is started over
+/// first, so every check is held to the limits on its own.
+///
+/// ```
+/// use diceroll::calculation::RollRequest;
+/// use diceroll::presets::coc::percentile_check;
+/// let mut request = RollRequest::new(rand::thread_rng());
+///
+/// // Spot Hidden 60 with one bonus die
+/// let check = percentile_check(&mut request, 60, 1).unwrap();
+/// assert_eq!(check.tens_dice.len(), 2);
+/// assert!(check.roll >= 1 && check.roll <= 100);
+/// ```
+pub fn percentile_check<R: Rng>(
+    request: &mut RollRequest<R>,
+    skill: i64,
+    bonus_dice: i64,
+) -> Result<PercentileCheck, &'static str> {
+    let bonus_dice = bonus_dice.clamp(-MAX_BONUS_DICE, MAX_BONUS_DICE);
+    request.reset().roll_dice(1, 10);
+    let units_die = dice(request)?[0] % 10;
+    request.roll_dice(1 + bonus_dice.unsigned_abs(), 10);
+    let tens_dice: Vec<i64> = dice(request)?.iter().map(|x| (x - 1) * 10).collect();
+
+    let candidates = tens_dice
+        .iter()
+        .map(|tens| percentile_roll(*tens, units_die));
+    let roll = if bonus_dice >= 0 {
+        candidates.min()
+    } else {
+        candidates.max()
+    }
+    .unwrap_or(100);
+
+    Ok(PercentileCheck {
+        roll,
+        tens_dice,
+        units_die,
+        level: SuccessLevel::from_roll(roll, skill),
+    })
+}
+
+/// A tens die of 00 and a units die of 0 read as 100
+fn percentile_roll(tens: i64, units: i64) -> i64 {
+    match tens + units {
+        0 => 100,
+        roll => roll,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculation::limits::EvalLimits;
+
+    #[test]
+    fn success_levels_follow_skill_thresholds() {
+        assert_eq!(SuccessLevel::from_roll(1, 50), SuccessLevel::Critical);
+        assert_eq!(SuccessLevel::from_roll(10, 50), SuccessLevel::Extreme);
+        assert_eq!(SuccessLevel::from_roll(11, 50), SuccessLevel::Hard);
+        assert_eq!(SuccessLevel::from_roll(25, 50), SuccessLevel::Hard);
+        assert_eq!(SuccessLevel::from_roll(50, 50), SuccessLevel::Regular);
+        assert_eq!(SuccessLevel::from_roll(51, 50), SuccessLevel::Failure);
+        assert_eq!(SuccessLevel::from_roll(99, 50), SuccessLevel::Failure);
+        assert_eq!(SuccessLevel::from_roll(100, 50), SuccessLevel::Fumble);
+    }
+
+    #[test]
+    fn low_skills_fumble_from_96() {
+        assert_eq!(SuccessLevel::from_roll(95, 49), SuccessLevel::Failure);
+        assert_eq!(SuccessLevel::from_roll(96, 49), SuccessLevel::Fumble);
+        assert_eq!(SuccessLevel::from_roll(1, 1), SuccessLevel::Critical);
+        assert!(!SuccessLevel::Fumble.is_success());
+        assert!(SuccessLevel::Hard.is_success());
+    }
+
+    #[test]
+    fn double_zero_reads_as_one_hundred() {
+        assert_eq!(percentile_roll(0, 0), 100);
+        assert_eq!(percentile_roll(0, 1), 1);
+        assert_eq!(percentile_roll(90, 0), 90);
+    }
+
+    #[test]
+    fn bonus_and_penalty_dice_roll_extra_tens_dice() {
+        // StepRng always rolls a 1, so every tens die is 00 and the units die is 1
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+
+        let check = percentile_check(&mut request, 40, -2).unwrap();
+        assert_eq!(
+            check,
+            PercentileCheck {
+                roll: 1,
+                tens_dice: vec![0, 0, 0],
+                units_die: 1,
+                level: SuccessLevel::Critical,
+            }
+        );
+        assert_eq!(
+            percentile_check(&mut request, 40, 0).unwrap().tens_dice,
+            vec![0]
+        );
+
+        // Anything past two bonus or penalty dice counts as two
+        assert_eq!(
+            percentile_check(&mut request, 40, i64::MIN)
+                .unwrap()
+                .tens_dice
+                .len(),
+            3
+        );
+        assert_eq!(
+            percentile_check(&mut request, 40, 5)
+                .unwrap()
+                .tens_dice
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn every_check_is_held_to_the_limits_on_its_own() {
+        let mut request = RollRequest::new(rand::thread_rng());
+        for _ in 0..60_000 {
+            assert!(percentile_check(&mut request, 50, 0).is_ok());
+        }
+
+        let mut request = RollRequest::new(rand::thread_rng());
+        request.with_limits(EvalLimits {
+            max_total_dice: 2,
+            ..EvalLimits::default()
+        });
+        assert_eq!(
+            percentile_check(&mut request, 50, 1),
+            Err("Too many dice rolled")
+        );
+    }
+}
@@ -0,0 +1,129 @@
+extern crate rand;
+
+use super::dice;
+use crate::calculation::comparison::ComparisonRoll;
+use crate::calculation::RollRequest;
+use rand::Rng;
+
+/// The action score can never be higher than 10
+pub const MAX_ACTION_SCORE: i64 = 10;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum IronswornResult {
+    StrongHit,
+    WeakHit,
+    Miss,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct IronswornRoll {
+    pub action_die: i64,
+    pub action_score: i64,
+    pub challenge_dice: Vec<i64>,
+    pub result: IronswornResult,
+    /// Both challenge dice show the same value
+    pub is_match: bool,
+}
+
+impl IronswornRoll {
+    /// Compare an action die plus `modifier` against the challenge dice. The action
+    /// score has to beat a challenge die, ties go to the challenge die. A
+    /// negative `modifier` is a penalty.
+    pub fn new(action_die: i64, modifier: i64, challenge_dice: Vec<i64>) -> IronswornRoll {
+        let action_score = action_die.saturating_add(modifier).min(MAX_ACTION_SCORE);
+        let comparison = ComparisonRoll::new(vec![action_score], challenge_dice);
+        let result = match comparison.wins() {
+            0 => IronswornResult::Miss,
+            1 => IronswornResult::WeakHit,
+            _ => IronswornResult::StrongHit,
+        };
+        let is_match = comparison.second.windows(2).all(|w| w[0] == w[1]);
+        IronswornRoll {
+            action_die,
+            action_score,
+            challenge_dice: comparison.second,
+            result,
+            is_match,
+        }
+    }
+}
+
+/// Roll a d6 action die plus `modifier` against two d10 challenge dice. The
+/// request is started over first, so every roll is held to the limits on its own.
+///
+/// ```
+/// use diceroll::calculation::RollRequest;
+/// use diceroll::presets::ironsworn::action_roll;
+/// let mut request = RollRequest::new(rand::thread_rng());
+///
+/// let roll = action_roll(&mut request, 2).unwrap();
+/// assert!(roll.action_score <= 8);
+/// assert_eq!(roll.challenge_dice.len(), 2);
+/// ```
+pub fn action_roll<R: Rng>(
+    request: &mut RollRequest<R>,
+    modifier: i64,
+) -> Result<IronswornRoll, &'static str> {
+    request.reset().roll_dice(1, 6);
+    let action_die = dice(request)?[0];
+    request.roll_dice(2, 10);
+    let challenge_dice = dice(request)?;
+    Ok(IronswornRoll::new(action_die, modifier, challenge_dice))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn action_score_has_to_beat_challenge_dice() {
+        assert_eq!(
+            IronswornRoll::new(4, 2, vec![3, 5]).result,
+            IronswornResult::StrongHit
+        );
+        assert_eq!(
+            IronswornRoll::new(4, 2, vec![6, 5]).result,
+            IronswornResult::WeakHit
+        );
+        assert_eq!(
+            IronswornRoll::new(4, 2, vec![6, 9]).result,
+            IronswornResult::Miss
+        );
+    }
+
+    #[test]
+    fn action_score_is_capped_at_ten() {
+        let roll = IronswornRoll::new(6, 7, vec![10, 10]);
+
+        assert_eq!(roll.action_score, 10);
+        assert_eq!(roll.result, IronswornResult::Miss);
+        assert!(roll.is_match);
+
+        let roll = action_roll(&mut RollRequest::new(rand::thread_rng()), i64::MAX).unwrap();
+        assert_eq!(roll.action_score, 10);
+    }
+
+    #[test]
+    fn penalties_lower_the_action_score() {
+        let roll = IronswornRoll::new(2, -3, vec![1, 1]);
+
+        assert_eq!(roll.action_score, -1);
+        assert_eq!(roll.result, IronswornResult::Miss);
+    }
+
+    #[test]
+    fn seeded_action_roll() {
+        let mut request = RollRequest::new(rand::rngs::mock::StepRng::new(2, 1));
+
+        assert_eq!(
+            action_roll(&mut request, 1),
+            Ok(IronswornRoll {
+                action_die: 1,
+                action_score: 2,
+                challenge_dice: vec![1, 1],
+                result: IronswornResult::StrongHit,
+                is_match: true,
+            })
+        );
+    }
+}
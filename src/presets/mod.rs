@@ -0,0 +1,20 @@
+use crate::calculation::die_source::DieSource;
+use crate::calculation::RollRequest;
+
+pub mod blades;
+pub mod coc;
+pub mod ironsworn;
+pub mod pbta;
+pub mod yze;
+
+/// The dice in the roll, or why there aren't any
+fn dice<R: DieSource>(request: &mut RollRequest<R>) -> Result<Vec<i64>, &'static str> {
+    if let Some(dice) = request.as_slice() {
+        return Ok(dice.to_vec());
+    }
+    match (request.failure(), request.exceeded()) {
+        (Some(message), _) => Err(message),
+        (None, Some(exceeded)) => Err(exceeded.message()),
+        (None, None) => Err("No dice left to roll!"),
+    }
+}
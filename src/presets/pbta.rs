@@ -0,0 +1,153 @@
+extern crate rand;
+
+use crate::controller::calculate_expression;
+use crate::parsing::{ComplexDiceRoll, DiceRoll, Expression, Operation, RollMod, RollModType};
+use rand::Rng;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PbtaTier {
+    StrongHit,
+    WeakHit,
+    Miss,
+}
+
+/// Advantage rolls 3d6 and keeps the highest two, disadvantage keeps the lowest two
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PbtaRollMode {
+    Normal,
+    Advantage,
+    Disadvantage,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct PbtaMove {
+    pub total: i64,
+    pub tier: PbtaTier,
+}
+
+impl PbtaTier {
+    /// 10 and up is a strong hit, 7-9 a weak hit and 6 or lower a miss
+    pub fn from_total(total: i64) -> PbtaTier {
+        match total {
+            t if t >= 10 => PbtaTier::StrongHit,
+            t if t >= 7 => PbtaTier::WeakHit,
+            _ => PbtaTier::Miss,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PbtaTier::StrongHit => "strong hit",
+            PbtaTier::WeakHit => "weak hit",
+            PbtaTier::Miss => "miss",
+        }
+    }
+}
+
+/// The `2d6+stat` expression for a move, or `3d6k2+stat`/`3d6l2+stat` when rolling
+/// with advantage or disadvantage
+pub fn move_expression(stat: i64, mode: PbtaRollMode) -> Expression {
+    let (number_of_dice, roll_mods) = match mode {
+        PbtaRollMode::Normal => (2, vec![]),
+        PbtaRollMode::Advantage => (3, vec![keep_two(RollModType::K)]),
+        PbtaRollMode::Disadvantage => (3, vec![keep_two(RollModType::L)]),
+    };
+    let roll = Expression::Roll(ComplexDiceRoll {
+        dice_roll: DiceRoll {
+            number_of_dice,
+            dice_range: 6,
+        },
+        roll_mods,
+    });
+    Expression::Operation(
+        Box::new(roll),
+        Operation::Add,
+        Box::new(Expression::Number(stat)),
+    )
+}
+
+/// Roll a move with the given stat and read the result. A stat so large that
+/// the total overflows is an error.
+///
+/// ```
+/// use diceroll::presets::pbta::{roll_move, PbtaRollMode};
+/// let mut rng = rand::thread_rng();
+///
+/// let result = roll_move(&mut rng, 2, PbtaRollMode::Normal).unwrap();
+/// assert!(result.total >= 4 && result.total <= 14);
+/// println!("{} ({})", result.total, result.tier.name());
+/// ```
+pub fn roll_move<R: Rng>(
+    rng: &mut R,
+    stat: i64,
+    mode: PbtaRollMode,
+) -> Result<PbtaMove, &'static str> {
+    let total = calculate_expression(&move_expression(stat, mode), rng)?.numerator();
+    Ok(PbtaMove {
+        total,
+        tier: PbtaTier::from_total(total),
+    })
+}
+
+fn keep_two(roll_mod_type: RollModType) -> RollMod {
+    RollMod {
+        roll_mod_type,
+        value: 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_map_to_tiers() {
+        assert_eq!(PbtaTier::from_total(12), PbtaTier::StrongHit);
+        assert_eq!(PbtaTier::from_total(10), PbtaTier::StrongHit);
+        assert_eq!(PbtaTier::from_total(9), PbtaTier::WeakHit);
+        assert_eq!(PbtaTier::from_total(7), PbtaTier::WeakHit);
+        assert_eq!(PbtaTier::from_total(6), PbtaTier::Miss);
+        assert_eq!(PbtaTier::from_total(-1), PbtaTier::Miss);
+    }
+
+    #[test]
+    fn stat_is_added_to_the_roll() {
+        let mut rng = rand::rngs::mock::StepRng::new(2, 1);
+
+        assert_eq!(
+            roll_move(&mut rng, 3, PbtaRollMode::Normal),
+            Ok(PbtaMove {
+                total: 5,
+                tier: PbtaTier::Miss
+            })
+        );
+        assert_eq!(
+            roll_move(&mut rng, -2, PbtaRollMode::Advantage).map(|result| result.total),
+            Ok(0)
+        );
+        assert_eq!(
+            roll_move(&mut rng, 8, PbtaRollMode::Disadvantage),
+            Ok(PbtaMove {
+                total: 10,
+                tier: PbtaTier::StrongHit
+            })
+        );
+    }
+
+    #[test]
+    fn extreme_stats_dont_panic() {
+        let mut rng = rand::rngs::mock::StepRng::new(2, 1);
+
+        assert_eq!(
+            roll_move(&mut rng, i64::MAX, PbtaRollMode::Normal),
+            Err("Integer overflow")
+        );
+        assert_eq!(
+            roll_move(&mut rng, i64::MIN, PbtaRollMode::Advantage),
+            Ok(PbtaMove {
+                total: i64::MIN + 2,
+                tier: PbtaTier::Miss
+            })
+        );
+    }
+}
@@ -0,0 +1,142 @@
+extern crate rand;
+
+use crate::calculation::stateful_roll::{RolledDie, StatefulRoll};
+use rand::Rng;
+
+pub const BASE: &str = "base";
+pub const SKILL: &str = "skill";
+pub const GEAR: &str = "gear";
+
+/// A Year Zero Engine roll of base, skill and gear d6 pools. Every 6 is a
+/// success, a 1 on a base die is a point of stress or damage, and a 1 on a gear
+/// die damages the gear. Pools too large to roll, or dice the generator can't
+/// give, are an error instead of a roll.
+///
+/// ```
+/// use diceroll::presets::yze::YearZeroRoll;
+/// let mut roll = YearZeroRoll::new(rand::thread_rng(), 3, 2, 1).unwrap();
+///
+/// if roll.successes() == 0 {
+///     roll.push().unwrap();
+/// }
+/// assert!(roll.is_pushed() || roll.successes() > 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct YearZeroRoll<R: Rng> {
+    roll: StatefulRoll<R>,
+    pushed: bool,
+    /// Where the history of the whole roll starts, after every pool is rolled
+    history_start: usize,
+}
+
+impl<R: Rng> YearZeroRoll<R> {
+    pub fn new(rng: R, base: u64, skill: u64, gear: u64) -> Result<YearZeroRoll<R>, &'static str> {
+        let mut roll = StatefulRoll::new(rng);
+        roll.roll(base, 6, BASE)
+            .roll(skill, 6, SKILL)
+            .roll(gear, 6, GEAR);
+        check(&roll)?;
+        Ok(YearZeroRoll {
+            history_start: roll.history().len() - 1,
+            roll,
+            pushed: false,
+        })
+    }
+
+    /// Push the roll, rerolling every die that isn't a 6 or a 1. A roll can only
+    /// be pushed once.
+    pub fn push(&mut self) -> Result<&mut YearZeroRoll<R>, &'static str> {
+        if self.pushed {
+            return Err("Roll has already been pushed");
+        }
+        self.roll
+            .reroll_where(|die| die.value != 6 && die.value != 1);
+        check(&self.roll)?;
+        self.pushed = true;
+        Ok(self)
+    }
+
+    pub fn is_pushed(&self) -> bool {
+        self.pushed
+    }
+
+    pub fn successes(&self) -> usize {
+        self.roll.dice().iter().filter(|die| die.value == 6).count()
+    }
+
+    /// The number of 1s on base dice
+    pub fn base_banes(&self) -> usize {
+        self.roll.pool(BASE).filter(|die| die.value == 1).count()
+    }
+
+    /// The number of 1s on gear dice
+    pub fn gear_banes(&self) -> usize {
+        self.roll.pool(GEAR).filter(|die| die.value == 1).count()
+    }
+
+    pub fn dice(&self) -> &[RolledDie] {
+        self.roll.dice()
+    }
+
+    /// The state of the dice before and after every push
+    pub fn history(&self) -> &[Vec<RolledDie>] {
+        &self.roll.history()[self.history_start..]
+    }
+}
+
+/// Why the dice couldn't be rolled, if they couldn't
+fn check<R: Rng>(roll: &StatefulRoll<R>) -> Result<(), &'static str> {
+    match (roll.failure(), roll.exceeded()) {
+        (Some(message), _) => Err(message),
+        (None, Some(exceeded)) => Err(exceeded.message()),
+        (None, None) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ones_on_base_and_gear_dice_are_banes() {
+        // StepRng always rolls a 1
+        let roll = YearZeroRoll::new(rand::rngs::mock::StepRng::new(2, 1), 3, 2, 1).unwrap();
+
+        assert_eq!(roll.dice().len(), 6);
+        assert_eq!(roll.successes(), 0);
+        assert_eq!(roll.base_banes(), 3);
+        assert_eq!(roll.gear_banes(), 1);
+    }
+
+    #[test]
+    fn roll_can_only_be_pushed_once() {
+        let mut roll = YearZeroRoll::new(rand::thread_rng(), 2, 2, 0).unwrap();
+
+        assert!(roll.push().is_ok());
+        assert!(roll.is_pushed());
+        assert_eq!(roll.push().err(), Some("Roll has already been pushed"));
+        assert_eq!(roll.history().len(), 2);
+        assert_eq!(roll.history()[0].len(), 4);
+    }
+
+    #[test]
+    fn push_keeps_sixes_and_ones() {
+        let mut roll = YearZeroRoll::new(rand::thread_rng(), 4, 4, 4).unwrap();
+        let before = roll.dice().to_vec();
+        roll.push().unwrap();
+
+        for (old, new) in before.iter().zip(roll.dice()) {
+            if old.value == 6 || old.value == 1 {
+                assert_eq!(old, new);
+            }
+        }
+    }
+
+    #[test]
+    fn pools_too_large_to_roll_are_an_error() {
+        assert_eq!(
+            YearZeroRoll::new(rand::thread_rng(), 1 << 40, 0, 0).err(),
+            Some("Too many dice in one roll")
+        );
+    }
+}